@@ -1,7 +1,8 @@
 use blstrs::Scalar as Fr;
-use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
 use ff::{Field, PrimeField};
-use generic_array::typenum;
+use generic_array::sequence::GenericSequence;
+use generic_array::{typenum, GenericArray};
 use neptune::poseidon::{HashMode, PoseidonConstants};
 use neptune::*;
 use pasta_curves::{Fp, Fq as Fv};
@@ -107,6 +108,27 @@ where
         },
     );
 
+    group.bench_with_input(
+        BenchmarkId::new("Poseidon hash optimized (scratch)", "Generated scalars"),
+        &scalars,
+        |b, s| {
+            let constants = PoseidonConstants::new_with_strength(Strength::Standard);
+            let mut h = Poseidon::<F, A>::new(&constants);
+            let mut scratch = GenericArray::generate(|_| F::ZERO);
+            b.iter(|| {
+                h.reset();
+                std::iter::repeat(())
+                    .take(A::to_usize())
+                    .map(|_| s.choose(&mut OsRng).unwrap())
+                    .for_each(|scalar| {
+                        h.input(*scalar).unwrap();
+                    });
+
+                h.hash_with_scratch(&mut scratch);
+            })
+        },
+    );
+
     group.bench_with_input(
         BenchmarkId::new(
             "Poseidon hash optimized (strengthened)",
@@ -139,7 +161,7 @@ criterion_group! {
     config = Criterion::default();
 
     targets = bench_hash_bls::<typenum::U2>, bench_hash_bls::<typenum::U4>,
-    bench_hash_bls::<typenum::U8>, bench_hash_bls::<typenum::U11>
+    bench_hash_bls::<typenum::U8>, bench_hash_bls::<typenum::U11>, bench_hash_bls::<typenum::U12>
 }
 
 fn bench_bls_and_pasta_fields_for_arity<A>(c: &mut Criterion)
@@ -179,4 +201,135 @@ criterion_group!(
     bench_bls_and_pasta_fields_for_arity::<U11>,
 );
 
-criterion_main!(hash_bls, bench_all_fields_for_common_arities);
+fn bench_batch_invert(c: &mut Criterion) {
+    // One element per lane for a batch of 1024 arity-4 hashes.
+    let elements: Vec<Fr> = fill_preimage(0, 1024 * 5);
+
+    let mut group = c.benchmark_group("invert-1024-arity-4");
+
+    group.bench_function("naive", |b| {
+        b.iter(|| {
+            elements
+                .iter()
+                .map(|x| x.invert().unwrap())
+                .collect::<Vec<_>>()
+        })
+    });
+
+    group.bench_function("batched", |b| {
+        b.iter(|| {
+            let mut v = elements.clone();
+            batch_invert(&mut v);
+            v
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(batch_invert_group, bench_batch_invert);
+
+fn bench_hash_into(c: &mut Criterion) {
+    let constants = PoseidonConstants::<Fr, U4>::new();
+    let preimages: Vec<Vec<Fr>> = (0..1024u64).map(|i| fill_preimage(i, 4)).collect();
+    let mut slots = vec![Fr::ZERO; preimages.len()];
+
+    let mut group = c.benchmark_group("batch-1024-arity-4");
+
+    group.bench_function("collect-and-copy", |b| {
+        b.iter(|| {
+            let mut p = Poseidon::new(&constants);
+            let digests: Vec<Fr> = preimages
+                .iter()
+                .map(|preimage| {
+                    p.set_preimage(preimage);
+                    p.hash()
+                })
+                .collect();
+            slots.copy_from_slice(&digests);
+        })
+    });
+
+    group.bench_function("hash-into", |b| {
+        b.iter(|| {
+            let mut p = Poseidon::new(&constants);
+            for (out, preimage) in slots.iter_mut().zip(&preimages) {
+                p.set_preimage(preimage);
+                p.hash_into(out);
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(hash_into_group, bench_hash_into);
+
+fn bench_wide_arity<A>(c: &mut Criterion, name: &str)
+where
+    A: Arity<Fr>,
+{
+    let constants = PoseidonConstants::<Fr, A>::new();
+    let preimages: Vec<Vec<Fr>> = (0..256u64)
+        .map(|i| (0..A::to_usize() as u64).map(|j| Fr::from(i + j)).collect())
+        .collect();
+
+    c.bench_function(name, |b| {
+        b.iter(|| {
+            let mut p = Poseidon::new(&constants);
+            for preimage in &preimages {
+                p.set_preimage(preimage);
+                black_box(p.hash());
+            }
+        })
+    });
+}
+
+fn bench_width_5(c: &mut Criterion) {
+    bench_wide_arity::<U4>(c, "layer-256-width-5");
+}
+
+fn bench_width_9(c: &mut Criterion) {
+    bench_wide_arity::<U8>(c, "layer-256-width-9");
+}
+
+criterion_group!(wide_arity_group, bench_width_5, bench_width_9);
+
+fn bench_deserialize(c: &mut Criterion) {
+    use bincode::Options;
+
+    let bytes = bincode::serialize(&PoseidonConstants::<Fr, U8>::new()).unwrap();
+    let options = || {
+        bincode::DefaultOptions::new()
+            .with_fixint_encoding()
+            .allow_trailing_bytes()
+    };
+
+    let mut group = c.benchmark_group("deserialize-bls-arity-8");
+
+    group.bench_function("full", |b| {
+        b.iter(|| bincode::deserialize::<PoseidonConstants<Fr, U8>>(&bytes).unwrap())
+    });
+
+    group.bench_function("hashing-only", |b| {
+        b.iter(|| {
+            PoseidonConstants::<Fr, U8>::deserialize_hashing_only(
+                &mut bincode::Deserializer::from_slice(&bytes, options()),
+            )
+            .unwrap()
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(deserialize_group, bench_deserialize);
+
+criterion_main!(
+    hash_bls,
+    bench_all_fields_for_common_arities,
+    batch_invert_group,
+    hash_into_group,
+    wide_arity_group,
+    deserialize_group
+);
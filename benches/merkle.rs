@@ -0,0 +1,45 @@
+use blstrs::Scalar as Fr;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use generic_array::typenum::U2;
+use neptune::merkle::{compress, merkle_root, par_merkle_root};
+use neptune::poseidon::{Poseidon, PoseidonConstants};
+
+fn bench_merkle_root(c: &mut Criterion) {
+    let constants = PoseidonConstants::<Fr, U2>::new();
+
+    let mut group = c.benchmark_group("merkle-root-bls");
+
+    for log_leaves in [10, 14] {
+        let leaves: Vec<Fr> = (0..1u64 << log_leaves).map(Fr::from).collect();
+
+        group.bench_with_input(
+            BenchmarkId::new("serial", log_leaves),
+            &leaves,
+            |b, leaves| b.iter(|| merkle_root(&constants, leaves).unwrap()),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("parallel", log_leaves),
+            &leaves,
+            |b, leaves| b.iter(|| par_merkle_root(&constants, leaves).unwrap()),
+        );
+    }
+
+    group.finish();
+}
+
+fn bench_compress(c: &mut Criterion) {
+    let constants = PoseidonConstants::<Fr, U2>::new();
+    let (left, right) = (Fr::from(1), Fr::from(2));
+
+    let mut group = c.benchmark_group("compress-bls");
+
+    group.bench_function("generic", |b| {
+        b.iter(|| Poseidon::new_with_preimage(&[left, right], &constants).hash())
+    });
+    group.bench_function("width-3", |b| b.iter(|| compress(&constants, left, right)));
+
+    group.finish();
+}
+
+criterion_group!(merkle, bench_merkle_root, bench_compress);
+criterion_main!(merkle);
@@ -0,0 +1,91 @@
+use blstrs::Scalar as Fr;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use generic_array::typenum::{U11, U2, U4, U8};
+use neptune::poseidon::PoseidonConstants;
+use neptune::Arity;
+
+/// Returns the number of field elements and of vectors in the serialized form of `constants`.
+fn serialized_counts<A: Arity<Fr>>(constants: &PoseidonConstants<Fr, A>) -> (usize, usize) {
+    let matrix = |m: &Vec<Vec<Fr>>| (m.iter().map(Vec::len).sum::<usize>(), 1 + m.len());
+    let mds = &constants.mds_matrices;
+    let matrices = [
+        &mds.m,
+        &mds.m_inv,
+        &mds.m_hat,
+        &mds.m_hat_inv,
+        &mds.m_prime,
+        &mds.m_double_prime,
+        &constants.pre_sparse_matrix,
+    ];
+
+    let (mut elements, mut vectors) = matrices
+        .iter()
+        .map(|m| matrix(m))
+        .fold((0, 0), |(e, v), (me, mv)| (e + me, v + mv));
+    elements += constants.compressed_round_constants.len();
+    vectors += 1;
+    for sparse in &constants.sparse_matrixes {
+        elements += sparse.w_hat.len() + sparse.v_rest.len();
+        vectors += 2;
+    }
+    vectors += 1;
+    (elements, vectors)
+}
+
+fn bench_serde<A: Arity<Fr>>(c: &mut Criterion) {
+    let constants = PoseidonConstants::<Fr, A>::new();
+    let (elements, vectors) = serialized_counts(&constants);
+
+    let bincode_bytes = bincode::serialize(&constants).unwrap();
+    let json_bytes = serde_json::to_vec(&constants).unwrap();
+
+    // Bincode writes elements as their 32-byte repr and each vector with an 8-byte length; the remaining fields take
+    // well under 64 bytes. serde_json writes elements as arrays of four u64s, so at most 86 bytes with a separator.
+    assert!(bincode_bytes.len() >= 32 * elements);
+    assert!(
+        bincode_bytes.len() <= 32 * elements + 8 * vectors + 64,
+        "bincode size of arity {} constants grew to {} bytes",
+        A::to_usize(),
+        bincode_bytes.len()
+    );
+    assert!(
+        json_bytes.len() <= 86 * elements + 3 * vectors + 256,
+        "serde_json size of arity {} constants grew to {} bytes",
+        A::to_usize(),
+        json_bytes.len()
+    );
+
+    let mut group = c.benchmark_group(format!("serde-{}", A::to_usize()));
+
+    group.bench_with_input(
+        BenchmarkId::new("bincode", bincode_bytes.len()),
+        &constants,
+        |b, constants| {
+            b.iter(|| {
+                let bytes = bincode::serialize(constants).unwrap();
+                bincode::deserialize::<PoseidonConstants<Fr, A>>(&bytes).unwrap()
+            })
+        },
+    );
+    group.bench_with_input(
+        BenchmarkId::new("serde_json", json_bytes.len()),
+        &constants,
+        |b, constants| {
+            b.iter(|| {
+                let bytes = serde_json::to_vec(constants).unwrap();
+                serde_json::from_slice::<PoseidonConstants<Fr, A>>(&bytes).unwrap()
+            })
+        },
+    );
+
+    group.finish();
+}
+
+criterion_group!(
+    serde,
+    bench_serde::<U2>,
+    bench_serde::<U4>,
+    bench_serde::<U8>,
+    bench_serde::<U11>
+);
+criterion_main!(serde);
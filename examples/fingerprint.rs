@@ -0,0 +1,24 @@
+//! Prints the fingerprints of the standard BLS12-381 constants for the common arities, so that deployments can check
+//! they hash with identical parameters.
+
+use blstrs::Scalar as Fr;
+use generic_array::typenum::{U11, U2, U4, U8};
+use neptune::poseidon::PoseidonConstants;
+use neptune::Arity;
+
+fn print_fingerprint<A: Arity<Fr>>() {
+    let constants = PoseidonConstants::<Fr, A>::new();
+    let hex: String = constants
+        .fingerprint()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    println!("arity {:>2}: {}", constants.arity(), hex);
+}
+
+fn main() {
+    print_fingerprint::<U2>();
+    print_fingerprint::<U4>();
+    print_fingerprint::<U8>();
+    print_fingerprint::<U11>();
+}
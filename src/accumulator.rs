@@ -0,0 +1,333 @@
+//! Sorted-set accumulators over binary Merkle trees.
+//!
+//! A [`SetAccumulator`] keeps a set of field elements sorted by their integer value, as the leaves of a [`MerkleTree`]
+//! which promotes odd nodes (see [`Padding::Promote`]). Its root is the compression of the tree root with the size of
+//! the set, so that proofs can check leaf indices against the size. Membership is proven by an inclusion proof of the
+//! element. Non-membership is proven by inclusion proofs of the adjacent leaves around the element: its predecessor
+//! and successor, or only one of them for elements below the minimum or above the maximum of the set.
+use crate::merkle::{compress, MerkleProof, MerkleTree, Padding};
+use crate::poseidon::PoseidonConstants;
+use crate::{ct_eq_digest, repr_is_little_endian};
+use ff::PrimeField;
+use generic_array::typenum::U2;
+use std::cmp::Ordering;
+
+/// Compares field elements by their integer value.
+pub fn cmp_elements<F: PrimeField>(a: &F, b: &F) -> Ordering {
+    let (a, b) = (a.to_repr(), b.to_repr());
+    if repr_is_little_endian::<F>() {
+        a.as_ref().iter().rev().cmp(b.as_ref().iter().rev())
+    } else {
+        a.as_ref().cmp(b.as_ref())
+    }
+}
+
+/// Returns the root of an accumulator of `size` elements whose tree has root `tree_root`, or no tree if the set is
+/// empty.
+pub fn accumulator_root<F: PrimeField>(
+    constants: &PoseidonConstants<F, U2>,
+    tree_root: Option<F>,
+    size: usize,
+) -> F {
+    compress(
+        constants,
+        tree_root.unwrap_or(F::ZERO),
+        F::from(size as u64),
+    )
+}
+
+/// A set of field elements with a Merkle root and proofs of membership and non-membership.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SetAccumulator<F: PrimeField> {
+    /// The elements, sorted by [`cmp_elements`] and without duplicates.
+    elements: Vec<F>,
+    tree: Option<MerkleTree<F>>,
+    root: F,
+}
+
+impl<F: PrimeField> SetAccumulator<F> {
+    /// Creates an empty accumulator.
+    pub fn new(constants: &PoseidonConstants<F, U2>) -> Self {
+        Self {
+            elements: Vec::new(),
+            tree: None,
+            root: accumulator_root(constants, None, 0),
+        }
+    }
+
+    /// Creates an accumulator of the given elements, ignoring duplicates.
+    pub fn from_elements(constants: &PoseidonConstants<F, U2>, elements: &[F]) -> Self {
+        let mut elements = elements.to_vec();
+        elements.sort_by(cmp_elements);
+        elements.dedup();
+
+        let mut accumulator = Self::new(constants);
+        accumulator.elements = elements;
+        accumulator.rebuild(constants);
+        accumulator
+    }
+
+    /// Inserts `element` and rebuilds the tree. Returns whether the element was not yet in the set; if it was, the
+    /// accumulator is unchanged.
+    pub fn insert(&mut self, constants: &PoseidonConstants<F, U2>, element: F) -> bool {
+        match self.position(&element) {
+            Ok(_) => false,
+            Err(index) => {
+                self.elements.insert(index, element);
+                self.rebuild(constants);
+                true
+            }
+        }
+    }
+
+    pub fn contains(&self, element: &F) -> bool {
+        self.position(element).is_ok()
+    }
+
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    /// Returns the elements in ascending order.
+    pub fn elements(&self) -> &[F] {
+        &self.elements
+    }
+
+    pub fn root(&self) -> F {
+        self.root
+    }
+
+    /// Returns a proof that `element` is in the set, or `None` if it is not.
+    pub fn prove_membership(&self, element: &F) -> Option<MembershipProof<F>> {
+        let index = self.position(element).ok()?;
+        Some(MembershipProof {
+            size: self.len(),
+            proof: self.leaf_proof(index),
+        })
+    }
+
+    /// Returns a proof that `element` is not in the set, or `None` if it is.
+    pub fn prove_non_membership(&self, element: &F) -> Option<NonMembershipProof<F>> {
+        let index = self.position(element).err()?;
+        Some(NonMembershipProof {
+            size: self.len(),
+            predecessor: index.checked_sub(1).map(|i| self.leaf_proof(i)),
+            successor: (index < self.len()).then(|| self.leaf_proof(index)),
+        })
+    }
+
+    fn position(&self, element: &F) -> Result<usize, usize> {
+        self.elements
+            .binary_search_by(|probe| cmp_elements(probe, element))
+    }
+
+    fn leaf_proof(&self, index: usize) -> MerkleProof<F> {
+        self.tree
+            .as_ref()
+            .and_then(|tree| tree.proof(index))
+            .expect("the tree holds a leaf for each element")
+    }
+
+    fn rebuild(&mut self, constants: &PoseidonConstants<F, U2>) {
+        self.tree = (!self.elements.is_empty()).then(|| {
+            MerkleTree::build_with_options(constants, &self.elements, Padding::Promote, false)
+                .expect("the set is not empty")
+        });
+        self.root = accumulator_root(
+            constants,
+            self.tree.as_ref().map(MerkleTree::root),
+            self.len(),
+        );
+    }
+}
+
+/// A proof that an element is in a [`SetAccumulator`] of `size` elements.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MembershipProof<F: PrimeField> {
+    pub size: usize,
+    pub proof: MerkleProof<F>,
+}
+
+impl<F: PrimeField> MembershipProof<F> {
+    /// Checks that `element` is in the set with the given accumulator root.
+    pub fn verify(&self, constants: &PoseidonConstants<F, U2>, root: F, element: &F) -> bool {
+        bool::from(ct_eq_digest(&self.proof.leaf, element))
+            && verify_leaf(constants, root, self.size, &self.proof)
+    }
+}
+
+/// A proof that an element is not in a [`SetAccumulator`] of `size` elements: inclusion proofs of the adjacent leaves
+/// below and above the element. The predecessor is absent for elements below the minimum, and the successor for
+/// elements above the maximum.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NonMembershipProof<F: PrimeField> {
+    pub size: usize,
+    pub predecessor: Option<MerkleProof<F>>,
+    pub successor: Option<MerkleProof<F>>,
+}
+
+impl<F: PrimeField> NonMembershipProof<F> {
+    /// Checks that `element` is not in the set with the given accumulator root.
+    pub fn verify(&self, constants: &PoseidonConstants<F, U2>, root: F, element: &F) -> bool {
+        let leaf_ok = |proof: &MerkleProof<F>| verify_leaf(constants, root, self.size, proof);
+
+        match (&self.predecessor, &self.successor) {
+            (Some(predecessor), Some(successor)) => {
+                successor.index == predecessor.index + 1
+                    && cmp_elements(&predecessor.leaf, element) == Ordering::Less
+                    && cmp_elements(element, &successor.leaf) == Ordering::Less
+                    && leaf_ok(predecessor)
+                    && leaf_ok(successor)
+            }
+            (None, Some(successor)) => {
+                successor.index == 0
+                    && cmp_elements(element, &successor.leaf) == Ordering::Less
+                    && leaf_ok(successor)
+            }
+            (Some(predecessor), None) => {
+                predecessor.index + 1 == self.size
+                    && cmp_elements(&predecessor.leaf, element) == Ordering::Less
+                    && leaf_ok(predecessor)
+            }
+            (None, None) => {
+                self.size == 0
+                    && bool::from(ct_eq_digest(&accumulator_root(constants, None, 0), &root))
+            }
+        }
+    }
+}
+
+/// Checks that `proof` is the inclusion proof of a leaf of a tree of `size` leaves under the accumulator `root`. The
+/// sibling of each layer must be present exactly when the tree shape gives the node one, so the proof binds the whole
+/// leaf index, including the bits of layers where the node is promoted.
+fn verify_leaf<F: PrimeField>(
+    constants: &PoseidonConstants<F, U2>,
+    root: F,
+    size: usize,
+    proof: &MerkleProof<F>,
+) -> bool {
+    if proof.index >= size {
+        return false;
+    }
+
+    let mut layer_len = size;
+    let mut depth = 0;
+    while layer_len > 1 {
+        let position = proof.index >> depth;
+        let has_sibling = (position ^ 1) < layer_len;
+        match proof.siblings.get(depth) {
+            Some(sibling) if sibling.is_some() == has_sibling => {}
+            _ => return false,
+        }
+        layer_len = (layer_len + 1) / 2;
+        depth += 1;
+    }
+    if proof.siblings.len() != depth {
+        return false;
+    }
+
+    let computed = accumulator_root(constants, Some(proof.root(constants)), size);
+    bool::from(ct_eq_digest(&computed, &root))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blstrs::Scalar as Fr;
+    use ff::Field;
+
+    fn accumulator(constants: &PoseidonConstants<Fr, U2>) -> SetAccumulator<Fr> {
+        let elements: Vec<Fr> = [40u64, 10, 30, 20, 50, 30].map(Fr::from).to_vec();
+        SetAccumulator::from_elements(constants, &elements)
+    }
+
+    #[test]
+    fn membership() {
+        let constants = PoseidonConstants::<Fr, U2>::new();
+        let set = accumulator(&constants);
+        assert_eq!(5, set.len());
+        assert_eq!(
+            [10u64, 20, 30, 40, 50].map(Fr::from).as_slice(),
+            set.elements()
+        );
+
+        for element in set.elements() {
+            let proof = set.prove_membership(element).unwrap();
+            assert!(proof.verify(&constants, set.root(), element));
+            assert!(!proof.verify(&constants, set.root(), &Fr::from(35)));
+            assert!(set.prove_non_membership(element).is_none());
+        }
+        assert!(set.prove_membership(&Fr::from(35)).is_none());
+
+        // The root commits to the size, so a proof for a smaller claimed size fails.
+        let mut proof = set.prove_membership(&Fr::from(10)).unwrap();
+        proof.size = 4;
+        assert!(!proof.verify(&constants, set.root(), &Fr::from(10)));
+    }
+
+    #[test]
+    fn non_membership() {
+        let constants = PoseidonConstants::<Fr, U2>::new();
+        let set = accumulator(&constants);
+
+        for x in [0u64, 9, 11, 25, 45, 51, 1000] {
+            let x = Fr::from(x);
+            let proof = set.prove_non_membership(&x).unwrap();
+            assert!(proof.verify(&constants, set.root(), &x));
+        }
+
+        // Boundaries: below the minimum only the successor is given, above the maximum only the predecessor.
+        let below = set.prove_non_membership(&Fr::from(5)).unwrap();
+        assert!(below.predecessor.is_none());
+        assert_eq!(0, below.successor.as_ref().unwrap().index);
+        let above = set.prove_non_membership(&-Fr::ONE).unwrap();
+        assert!(above.successor.is_none());
+        assert_eq!(4, above.predecessor.as_ref().unwrap().index);
+
+        // A proof does not carry over to members or to elements outside its gap.
+        let gap = set.prove_non_membership(&Fr::from(25)).unwrap();
+        assert!(!gap.verify(&constants, set.root(), &Fr::from(20)));
+        assert!(!gap.verify(&constants, set.root(), &Fr::from(35)));
+
+        // Leaves which are not adjacent do not prove non-membership.
+        let mut skipping = gap.clone();
+        skipping.successor = set.prove_membership(&Fr::from(40)).map(|p| p.proof);
+        assert!(!skipping.verify(&constants, set.root(), &Fr::from(25)));
+
+        // The maximum cannot be passed off as the last leaf of a larger set.
+        let mut above = above;
+        above.size = 6;
+        assert!(!above.verify(&constants, set.root(), &-Fr::ONE));
+    }
+
+    #[test]
+    fn insert_and_empty() {
+        let constants = PoseidonConstants::<Fr, U2>::new();
+        let mut set = SetAccumulator::new(&constants);
+        assert!(set.is_empty());
+        let x = Fr::from(7);
+        assert!(set
+            .prove_non_membership(&x)
+            .unwrap()
+            .verify(&constants, set.root(), &x));
+
+        assert!(set.insert(&constants, x));
+        assert!(!set.insert(&constants, x));
+        assert!(set.contains(&x));
+        assert!(set
+            .prove_membership(&x)
+            .unwrap()
+            .verify(&constants, set.root(), &x));
+
+        for element in [50u64, 10, 40, 20, 30] {
+            set.insert(&constants, Fr::from(element));
+        }
+        let mut expected = accumulator(&constants);
+        expected.insert(&constants, x);
+        assert_eq!(expected.root(), set.root());
+    }
+}
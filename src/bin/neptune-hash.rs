@@ -0,0 +1,33 @@
+//! Hashes groups of hex-encoded BLS12-381 scalars read from standard input, writing one digest per line.
+//!
+//! Usage: `neptune-hash [ARITY]`, where `ARITY` is 2 (the default), 4 or 8.
+use blstrs::Scalar as Fr;
+use generic_array::typenum::{U2, U4, U8};
+use neptune::hex::hash_hex_lines;
+use neptune::poseidon::PoseidonConstants;
+use std::io::{self, BufWriter};
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let arity = std::env::args().nth(1).unwrap_or_else(|| "2".to_string());
+
+    let stdin = io::stdin().lock();
+    let stdout = BufWriter::new(io::stdout().lock());
+    let result = match arity.as_str() {
+        "2" => hash_hex_lines(&PoseidonConstants::<Fr, U2>::new(), stdin, stdout),
+        "4" => hash_hex_lines(&PoseidonConstants::<Fr, U4>::new(), stdin, stdout),
+        "8" => hash_hex_lines(&PoseidonConstants::<Fr, U8>::new(), stdin, stdout),
+        _ => {
+            eprintln!("unsupported arity {arity:?}, expected 2, 4 or 8");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,570 @@
+//! Conversions between byte strings and field elements.
+//!
+//! `neptune` encodes field elements as little-endian bytes, which for most supported fields is the field's `Repr`
+//! (the `Repr` of the secp256k1 scalar field is big-endian, and is byte-swapped by these conversions). Clients which
+//! encode field elements as big-endian byte strings must convert them with [`scalar_from_bytes_be`] (or hash
+//! them with [`hash_bytes_with_endianness`] and [`Endianness::Big`]): feeding byte-swapped elements to the
+//! little-endian conversion silently yields different elements, and therefore different digests.
+use crate::poseidon::{hash_exact, hash_salted, Arity, Poseidon, PoseidonConstants};
+use crate::Error;
+use ff::PrimeField;
+
+/// Byte order of an encoded field element.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Endianness {
+    Little,
+    Big,
+}
+
+/// Decodes a field element from exactly `F::Repr`-many little-endian bytes. Returns an error if the length is wrong
+/// or the value is not canonical (not less than the modulus).
+pub fn scalar_from_bytes_le<F: PrimeField>(bytes: &[u8]) -> Result<F, Error> {
+    let mut repr = F::Repr::default();
+    if bytes.len() != repr.as_ref().len() {
+        return Err(Error::Other(format!(
+            "expected {} bytes for a field element, got {}",
+            repr.as_ref().len(),
+            bytes.len()
+        )));
+    }
+    repr.as_mut().copy_from_slice(bytes);
+    if !crate::repr_is_little_endian::<F>() {
+        repr.as_mut().reverse();
+    }
+
+    Option::from(F::from_repr(repr))
+        .ok_or_else(|| Error::Other("bytes are not a canonical field element".to_string()))
+}
+
+/// Decodes a field element from exactly `F::Repr`-many big-endian bytes. See [`scalar_from_bytes_le`].
+pub fn scalar_from_bytes_be<F: PrimeField>(bytes: &[u8]) -> Result<F, Error> {
+    let mut le = bytes.to_vec();
+    le.reverse();
+    scalar_from_bytes_le(&le)
+}
+
+/// Decodes a field element from bytes in the given byte order.
+pub fn scalar_from_bytes<F: PrimeField>(bytes: &[u8], endianness: Endianness) -> Result<F, Error> {
+    match endianness {
+        Endianness::Little => scalar_from_bytes_le(bytes),
+        Endianness::Big => scalar_from_bytes_be(bytes),
+    }
+}
+
+/// Encodes a field element as little-endian bytes.
+pub fn scalar_to_bytes_le<F: PrimeField>(scalar: &F) -> Vec<u8> {
+    let mut bytes = scalar.to_repr().as_ref().to_vec();
+    if !crate::repr_is_little_endian::<F>() {
+        bytes.reverse();
+    }
+    bytes
+}
+
+/// Encodes a field element as big-endian bytes.
+pub fn scalar_to_bytes_be<F: PrimeField>(scalar: &F) -> Vec<u8> {
+    let mut bytes = scalar_to_bytes_le(scalar);
+    bytes.reverse();
+    bytes
+}
+
+/// Hashes a byte string holding consecutive field elements, each encoded in `endianness` byte order and taking up
+/// exactly `F::Repr`-many bytes. The elements are hashed as a constant-length preimage, exactly as
+/// [`hash_exact`] would hash the decoded elements. Returns an error if the length of `bytes` is not a multiple of the
+/// element size, or if any element is not canonical.
+pub fn hash_bytes_with_endianness<F, A>(
+    constants: &PoseidonConstants<F, A>,
+    bytes: &[u8],
+    endianness: Endianness,
+) -> Result<F, Error>
+where
+    F: PrimeField,
+    A: Arity<F>,
+{
+    let element_len = F::Repr::default().as_ref().len();
+    if bytes.len() % element_len != 0 {
+        return Err(Error::Other(format!(
+            "byte length {} is not a multiple of the field element size {}",
+            bytes.len(),
+            element_len
+        )));
+    }
+
+    let elements = bytes
+        .chunks(element_len)
+        .map(|chunk| scalar_from_bytes(chunk, endianness))
+        .collect::<Result<Vec<F>, Error>>()?;
+
+    hash_exact(constants, elements.into_iter())
+}
+
+/// Number of bytes packed into each field element by [`pack_bytes`]: the largest number of bytes which always fits
+/// below the modulus.
+pub fn packed_bytes_per_element<F: PrimeField>() -> usize {
+    F::CAPACITY as usize / 8
+}
+
+/// Packs arbitrary bytes into field elements. A single `0x01` byte is appended before the bytes are split into
+/// little-endian chunks of [`packed_bytes_per_element`] bytes, the last of which is zero-padded. The padding makes the
+/// packing injective: byte strings differing only in trailing zeros pack to different elements.
+pub fn pack_bytes<F: PrimeField>(bytes: &[u8]) -> Vec<F> {
+    let mut padded = Vec::with_capacity(bytes.len() + 1);
+    padded.extend_from_slice(bytes);
+    padded.push(1);
+
+    padded
+        .chunks(packed_bytes_per_element::<F>())
+        .map(pack_chunk)
+        .collect()
+}
+
+fn pack_chunk<F: PrimeField>(chunk: &[u8]) -> F {
+    let mut repr = F::Repr::default();
+    repr.as_mut()[..chunk.len()].copy_from_slice(chunk);
+    if !crate::repr_is_little_endian::<F>() {
+        repr.as_mut().reverse();
+    }
+    Option::from(F::from_repr(repr)).expect("packed chunks are below the modulus")
+}
+
+/// Hashes arbitrary bytes, packed into field elements with [`pack_bytes`], as a constant-length preimage.
+pub fn hash_bytes<F, A>(constants: &PoseidonConstants<F, A>, bytes: &[u8]) -> F
+where
+    F: PrimeField,
+    A: Arity<F>,
+{
+    hash_exact(constants, pack_bytes(bytes).into_iter())
+        .expect("packed elements have the reported length")
+}
+
+/// Returns the field element derived from `context` by [`hash_with_context`]: the [`hash_bytes`] digest of the context.
+/// Hashing many preimages under one context can compute it once and pass it to [`hash_salted`] as the salt.
+pub fn context_tag<F, A>(constants: &PoseidonConstants<F, A>, context: &[u8]) -> F
+where
+    F: PrimeField,
+    A: Arity<F>,
+{
+    hash_bytes(constants, context)
+}
+
+/// Hashes `preimage` in the application context named by the bytes `context`. The context is hashed to a field
+/// element with [`context_tag`], which is added to the domain tag of `constants` in the capacity element, as
+/// [`hash_salted`] does. Different contexts thus give independent hash functions over the same preimages.
+///
+/// Every context, including the empty one, yields a non-trivial tag, since [`pack_bytes`] packs even empty byte
+/// strings into one element. Digests under the empty context therefore differ from those of [`Poseidon::hash`].
+///
+/// # Panics
+///
+/// Panics if the length of `preimage` is invalid for the hash type, as [`Poseidon::new_with_preimage`] does.
+pub fn hash_with_context<F, A>(
+    constants: &PoseidonConstants<F, A>,
+    context: &[u8],
+    preimage: &[F],
+) -> F
+where
+    F: PrimeField,
+    A: Arity<F>,
+{
+    hash_salted(constants, context_tag(constants, context), preimage)
+}
+
+/// Computes the root of a Merkle tree over arbitrary bytes, for storage-proof style commitments to large blobs.
+///
+/// The bytes are packed into field elements as by [`pack_bytes`], so the root is injective in `data` including its
+/// length. Each consecutive group of arity elements forms a leaf, hashed with `constants`; the last leaf is padded with
+/// zero elements. Leaves are combined into a tree of arity `A`, with missing nodes of an incomplete tree taken to be
+/// zero. A single leaf is its own root.
+///
+/// The data is processed `chunk_leaves` leaves at a time, and the tree is built incrementally, so memory use is
+/// bounded by the chunk size and the depth of the tree rather than by the length of `data`. The root does not depend
+/// on `chunk_leaves`.
+///
+/// # Panics
+///
+/// Panics if `chunk_leaves` is zero, or if `constants` are not for Merkle tree hashes.
+pub fn hash_blob<F, A>(constants: &PoseidonConstants<F, A>, data: &[u8], chunk_leaves: usize) -> F
+where
+    F: PrimeField,
+    A: Arity<F>,
+{
+    assert!(chunk_leaves > 0, "chunks must hold at least one leaf");
+    let arity = A::to_usize();
+    let per_element = packed_bytes_per_element::<F>();
+    let chunk_len = chunk_leaves * arity * per_element;
+
+    let mut tree = StreamingTree::new(constants);
+    let (full, rest) = data.split_at(data.len() - data.len() % chunk_len);
+    for chunk in full.chunks(chunk_len) {
+        tree.push_leaves(chunk);
+    }
+    // The final chunk is never empty: it holds at least the padding byte.
+    let mut last = Vec::with_capacity(rest.len() + 1);
+    last.extend_from_slice(rest);
+    last.push(1);
+    tree.push_leaves(&last);
+
+    tree.root()
+}
+
+/// A Merkle tree built leaf by leaf, holding fewer than arity nodes per level.
+struct StreamingTree<'a, F, A>
+where
+    F: PrimeField,
+    A: Arity<F>,
+{
+    poseidon: Poseidon<'a, F, A>,
+    /// Nodes of each level, starting from the leaves, which are not yet combined into their parent.
+    levels: Vec<Vec<F>>,
+}
+
+impl<'a, F, A> StreamingTree<'a, F, A>
+where
+    F: PrimeField,
+    A: Arity<F>,
+{
+    fn new(constants: &'a PoseidonConstants<F, A>) -> Self {
+        Self {
+            poseidon: Poseidon::new(constants),
+            levels: Vec::new(),
+        }
+    }
+
+    /// Packs `bytes` and pushes the resulting leaves. Unless this is the last call, the number of bytes must be a
+    /// multiple of the number of bytes per leaf.
+    fn push_leaves(&mut self, bytes: &[u8]) {
+        let arity = A::to_usize();
+        let elements: Vec<F> = bytes
+            .chunks(packed_bytes_per_element::<F>())
+            .map(pack_chunk)
+            .collect();
+
+        for group in elements.chunks(arity) {
+            let leaf = self.hash_padded(group);
+            self.push(0, leaf);
+        }
+    }
+
+    fn push(&mut self, level: usize, node: F) {
+        if level == self.levels.len() {
+            self.levels.push(Vec::with_capacity(A::to_usize()));
+        }
+        self.levels[level].push(node);
+
+        if self.levels[level].len() == A::to_usize() {
+            let nodes = std::mem::take(&mut self.levels[level]);
+            let parent = self.hash_padded(&nodes);
+            self.push(level + 1, parent);
+        }
+    }
+
+    fn hash_padded(&mut self, nodes: &[F]) -> F {
+        let mut preimage = nodes.to_vec();
+        preimage.resize(A::to_usize(), F::ZERO);
+        self.poseidon.set_preimage(&preimage);
+        self.poseidon.hash()
+    }
+
+    /// Combines the remaining nodes, padding incomplete levels with zeros.
+    fn root(mut self) -> F {
+        let top = self.levels.len() - 1;
+        let mut carry = None;
+        for level in 0..=top {
+            let mut nodes = std::mem::take(&mut self.levels[level]);
+            nodes.extend(carry);
+            carry = match nodes.len() {
+                0 => None,
+                1 if level == top => Some(nodes[0]),
+                _ => Some(self.hash_padded(&nodes)),
+            };
+        }
+        carry.expect("the tree has at least one leaf")
+    }
+}
+
+/// A streaming hash interface in the style of the `digest` crate, for code which is generic over hash functions.
+pub trait ByteHasher {
+    type Output;
+
+    /// Appends `data` to the input.
+    fn update(&mut self, data: &[u8]);
+
+    /// Returns the digest of all the input.
+    fn finalize(self) -> Self::Output;
+}
+
+/// Streaming adapter for [`hash_bytes`]: the digest of all updates equals [`hash_bytes`] of their concatenation.
+/// Since the preimage is hashed with a constant-length domain tag, its packed elements are buffered until
+/// [`ByteHasher::finalize`].
+#[derive(Clone, Debug)]
+pub struct PoseidonHasher<'a, F, A>
+where
+    F: PrimeField,
+    A: Arity<F>,
+{
+    constants: &'a PoseidonConstants<F, A>,
+    elements: Vec<F>,
+    /// Bytes not yet packed, always fewer than [`packed_bytes_per_element`].
+    pending: Vec<u8>,
+}
+
+impl<'a, F, A> PoseidonHasher<'a, F, A>
+where
+    F: PrimeField,
+    A: Arity<F>,
+{
+    pub fn new(constants: &'a PoseidonConstants<F, A>) -> Self {
+        Self {
+            constants,
+            elements: Vec::new(),
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl<'a, F, A> ByteHasher for PoseidonHasher<'a, F, A>
+where
+    F: PrimeField,
+    A: Arity<F>,
+{
+    type Output = F;
+
+    fn update(&mut self, data: &[u8]) {
+        let chunk_len = packed_bytes_per_element::<F>();
+        self.pending.extend_from_slice(data);
+
+        let complete = self.pending.len() - self.pending.len() % chunk_len;
+        self.elements.extend(
+            self.pending[..complete]
+                .chunks(chunk_len)
+                .map(pack_chunk::<F>),
+        );
+        self.pending.drain(..complete);
+    }
+
+    fn finalize(mut self) -> F {
+        self.pending.push(1);
+        self.elements.push(pack_chunk(&self.pending));
+        hash_exact(self.constants, self.elements.into_iter())
+            .expect("packed elements have the reported length")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blstrs::Scalar as Fr;
+    use ff::Field;
+    use generic_array::typenum::{U2, U4};
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    #[test]
+    fn roundtrip_both_endiannesses() {
+        let mut rng = XorShiftRng::from_seed(crate::TEST_SEED);
+        for _ in 0..100 {
+            let x = Fr::random(&mut rng);
+
+            let le = scalar_to_bytes_le(&x);
+            let be = scalar_to_bytes_be(&x);
+            assert_eq!(x, scalar_from_bytes_le::<Fr>(&le).unwrap());
+            assert_eq!(x, scalar_from_bytes_be::<Fr>(&be).unwrap());
+            assert_eq!(le.iter().rev().copied().collect::<Vec<_>>(), be);
+        }
+
+        let one_be = {
+            let mut bytes = vec![0u8; 32];
+            bytes[31] = 1;
+            bytes
+        };
+        assert_eq!(Fr::ONE, scalar_from_bytes_be::<Fr>(&one_be).unwrap());
+        // Feeding big-endian bytes to the little-endian conversion gives a different element.
+        assert_ne!(
+            Ok(Fr::ONE),
+            scalar_from_bytes_le::<Fr>(&one_be).map_err(|_| ())
+        );
+
+        assert!(scalar_from_bytes_le::<Fr>(&[0u8; 31]).is_err());
+        assert!(scalar_from_bytes_le::<Fr>(&[0xffu8; 32]).is_err());
+    }
+
+    #[cfg(feature = "secp256k1")]
+    #[test]
+    fn big_endian_repr() {
+        use k256::Scalar as K;
+
+        let x = K::from(0x0102u64);
+        let mut le = vec![0u8; 32];
+        le[..2].copy_from_slice(&[2, 1]);
+        assert_eq!(le, scalar_to_bytes_le(&x));
+        assert_eq!(x, scalar_from_bytes_le::<K>(&le).unwrap());
+        assert_eq!(
+            x,
+            scalar_from_bytes_be::<K>(&scalar_to_bytes_be(&x)).unwrap()
+        );
+        assert_eq!(vec![K::from(0x01_0203u64)], pack_bytes::<K>(&[3, 2]));
+    }
+
+    #[test]
+    fn hash_bytes_matches_decoded_elements() {
+        let constants = PoseidonConstants::<Fr, U4>::new();
+        let elements: Vec<Fr> = (0..6u64).map(Fr::from).collect();
+
+        let le: Vec<u8> = elements.iter().flat_map(scalar_to_bytes_le).collect();
+        let be: Vec<u8> = elements.iter().flat_map(scalar_to_bytes_be).collect();
+
+        let expected = hash_exact(&constants, elements.into_iter()).unwrap();
+        assert_eq!(
+            expected,
+            hash_bytes_with_endianness(&constants, &le, Endianness::Little).unwrap()
+        );
+        assert_eq!(
+            expected,
+            hash_bytes_with_endianness(&constants, &be, Endianness::Big).unwrap()
+        );
+        assert_ne!(
+            expected,
+            hash_bytes_with_endianness(&constants, &be, Endianness::Little).unwrap()
+        );
+
+        assert!(hash_bytes_with_endianness(&constants, &le[1..], Endianness::Little).is_err());
+    }
+
+    #[test]
+    fn pack_bytes_is_injective() {
+        let per_element = packed_bytes_per_element::<Fr>();
+        assert_eq!(31, per_element);
+
+        assert_eq!(vec![Fr::ONE], pack_bytes::<Fr>(&[]));
+        assert_ne!(pack_bytes::<Fr>(b"ab"), pack_bytes::<Fr>(b"ab\0"));
+        assert_eq!(1, pack_bytes::<Fr>(&[7; 30]).len());
+        assert_eq!(2, pack_bytes::<Fr>(&[7; 31]).len());
+    }
+
+    /// Builds the tree of [`hash_blob`] level by level, with all leaves in memory.
+    fn blob_root_reference<A: Arity<Fr>>(constants: &PoseidonConstants<Fr, A>, data: &[u8]) -> Fr {
+        let arity = A::to_usize();
+        let hash_row = |row: &[Fr]| -> Vec<Fr> {
+            row.chunks(arity)
+                .map(|group| {
+                    let mut preimage = group.to_vec();
+                    preimage.resize(arity, Fr::ZERO);
+                    Poseidon::new_with_preimage(&preimage, constants).hash()
+                })
+                .collect()
+        };
+
+        let mut row = hash_row(&pack_bytes(data));
+        while row.len() > 1 {
+            row = hash_row(&row);
+        }
+        row[0]
+    }
+
+    #[test]
+    fn hash_blob_matches_reference() {
+        let constants2 = PoseidonConstants::<Fr, U2>::new();
+        let constants4 = PoseidonConstants::<Fr, U4>::new();
+        let data: Vec<u8> = (0..5000u32).map(|i| (i * 31 + 7) as u8).collect();
+
+        // Lengths around leaf, chunk and tree boundaries, none of which need to be aligned.
+        for len in [0, 1, 61, 62, 63, 124, 125, 4096, 5000] {
+            let expected = blob_root_reference(&constants2, &data[..len]);
+            for chunk_leaves in [1, 3, 16] {
+                assert_eq!(
+                    expected,
+                    hash_blob(&constants2, &data[..len], chunk_leaves),
+                    "wrong root for {} bytes in chunks of {} leaves",
+                    len,
+                    chunk_leaves
+                );
+            }
+            assert_eq!(
+                blob_root_reference(&constants4, &data[..len]),
+                hash_blob(&constants4, &data[..len], 5)
+            );
+        }
+
+        // A single leaf is its own root.
+        assert_eq!(
+            Poseidon::new_with_preimage(&[pack_bytes(b"abc")[0], Fr::ZERO], &constants2).hash(),
+            hash_blob(&constants2, b"abc", 1)
+        );
+    }
+
+    #[test]
+    fn hash_blob_is_deterministic() {
+        let constants = PoseidonConstants::<Fr, U4>::new();
+        let data: Vec<u8> = (0..3000u32).map(|i| (i % 251) as u8).collect();
+
+        assert_eq!(
+            hash_blob(&constants, &data, 4),
+            hash_blob(&constants, &data, 4)
+        );
+        assert_ne!(
+            hash_blob(&constants, &data[..2999], 4),
+            hash_blob(&constants, &data, 4)
+        );
+
+        let mut zero_extended = data.clone();
+        zero_extended.push(0);
+        assert_ne!(
+            hash_blob(&constants, &data, 4),
+            hash_blob(&constants, &zero_extended, 4)
+        );
+    }
+
+    #[test]
+    fn hasher_matches_hash_bytes() {
+        let constants = PoseidonConstants::<Fr, U4>::new();
+        let message: Vec<u8> = (0..200u8).collect();
+
+        for len in [0, 1, 30, 31, 32, 62, 200] {
+            let expected = hash_bytes(&constants, &message[..len]);
+
+            let mut hasher = PoseidonHasher::new(&constants);
+            for piece in message[..len].chunks(7) {
+                hasher.update(piece);
+            }
+            assert_eq!(
+                expected,
+                hasher.finalize(),
+                "wrong digest for {} bytes",
+                len
+            );
+        }
+
+        assert_ne!(
+            hash_bytes(&constants, b"neptune"),
+            hash_bytes(&constants, b"neptune\0")
+        );
+    }
+
+    #[test]
+    fn context_separation() {
+        let constants = PoseidonConstants::<Fr, U2>::new();
+        let preimage = [Fr::from(1), Fr::from(2)];
+
+        let digest = hash_with_context(&constants, b"app-a", &preimage);
+        assert_eq!(digest, hash_with_context(&constants, b"app-a", &preimage));
+        assert_eq!(
+            digest,
+            hash_salted(&constants, context_tag(&constants, b"app-a"), &preimage)
+        );
+
+        let others = [
+            hash_with_context(&constants, b"app-b", &preimage),
+            hash_with_context(&constants, b"app-a\0", &preimage),
+            hash_with_context(&constants, b"", &preimage),
+            hash_with_context(&constants, b"app-a", &[Fr::from(2), Fr::from(1)]),
+        ];
+        for other in &others {
+            assert_ne!(digest, *other);
+        }
+
+        // The empty context is distinct from the standard hash.
+        assert_ne!(
+            Poseidon::new_with_preimage(&preimage, &constants).hash(),
+            hash_with_context(&constants, b"", &preimage)
+        );
+    }
+}
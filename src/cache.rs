@@ -0,0 +1,120 @@
+//! Process-wide memoization of [`PoseidonConstants`].
+//!
+//! Generating constants is expensive, so applications which need the same constants in many places can use
+//! [`cached_constants`] to generate them at most once per process and share them via [`Arc`].
+use crate::poseidon::{Arity, PoseidonConstants};
+use crate::Strength;
+use ff::PrimeField;
+use generic_array::typenum::{U11, U2, U4, U8};
+use once_cell::sync::Lazy;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+type CacheKey = (TypeId, usize, Strength);
+
+static CACHE: Lazy<Mutex<HashMap<CacheKey, Arc<dyn Any + Send + Sync>>>> =
+    Lazy::new(Default::default);
+
+/// Returns the Merkle tree [`PoseidonConstants`] for the given field, arity and strength, generating them on first
+/// use. Subsequent calls with the same parameters return the same [`Arc`], from any thread.
+///
+/// # Example
+///
+/// ```
+/// use neptune::cache::cached_constants;
+/// use neptune::Strength;
+/// use pasta_curves::Fp;
+/// use generic_array::typenum::U2;
+/// use std::sync::Arc;
+///
+/// let a = cached_constants::<Fp, U2>(Strength::Standard);
+/// let b = cached_constants::<Fp, U2>(Strength::Standard);
+///
+/// assert!(Arc::ptr_eq(&a, &b));
+/// ```
+pub fn cached_constants<F, A>(strength: Strength) -> Arc<PoseidonConstants<F, A>>
+where
+    F: PrimeField,
+    A: Arity<F> + Send + Sync + 'static,
+{
+    let key = (TypeId::of::<F>(), A::to_usize(), strength);
+
+    // The lock is held while generating, so that concurrent callers never generate the same constants twice.
+    let mut cache = CACHE.lock().expect("constants cache poisoned");
+    let entry = cache
+        .entry(key)
+        .or_insert_with(|| Arc::new(PoseidonConstants::<F, A>::new_with_strength(strength)));
+
+    entry
+        .clone()
+        .downcast::<PoseidonConstants<F, A>>()
+        .expect("constants cache entry has wrong type")
+}
+
+/// Generates and caches the constants for the preset arities 2, 4, 8 and 11 over `F` at the given strength.
+///
+/// Constants are otherwise generated on first use, which for latency-sensitive services means the first hash of each
+/// arity takes much longer than the rest. Calling this at startup moves that cost out of the request path; later
+/// calls to [`cached_constants`] for these parameters return immediately.
+pub fn warm_up<F: PrimeField>(strength: Strength) {
+    cached_constants::<F, U2>(strength);
+    cached_constants::<F, U4>(strength);
+    cached_constants::<F, U8>(strength);
+    cached_constants::<F, U11>(strength);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poseidon::Poseidon;
+    use blstrs::Scalar as Fr;
+    use pasta_curves::pallas::Scalar as S1;
+
+    #[test]
+    fn cached_constants_are_shared() {
+        let a = cached_constants::<Fr, U2>(Strength::Standard);
+        let b = cached_constants::<Fr, U2>(Strength::Standard);
+        assert!(Arc::ptr_eq(&a, &b));
+        assert_eq!(*a, PoseidonConstants::<Fr, U2>::new());
+
+        let strengthened = cached_constants::<Fr, U2>(Strength::Strengthened);
+        assert_eq!(strengthened.strength, Strength::Strengthened);
+
+        let wider = cached_constants::<Fr, U4>(Strength::Standard);
+        assert_eq!(wider.arity(), 4);
+
+        // Same arity and strength over another field is a distinct entry.
+        let pallas = cached_constants::<S1, U2>(Strength::Standard);
+        assert_eq!(*pallas, PoseidonConstants::<S1, U2>::new());
+    }
+
+    #[test]
+    fn cached_constants_across_threads() {
+        let handles = (0..4)
+            .map(|_| std::thread::spawn(|| cached_constants::<Fr, U2>(Strength::Standard)))
+            .collect::<Vec<_>>();
+        let all = handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .collect::<Vec<_>>();
+
+        for constants in &all {
+            assert!(Arc::ptr_eq(&all[0], constants));
+        }
+    }
+
+    #[test]
+    fn warm_up_then_hash() {
+        let preimage = [Fr::from(1), Fr::from(2), Fr::from(3), Fr::from(4)];
+        let expected =
+            Poseidon::new_with_preimage(&preimage, &PoseidonConstants::<Fr, U4>::new()).hash();
+
+        warm_up::<Fr>(Strength::Standard);
+        let constants = cached_constants::<Fr, U4>(Strength::Standard);
+        assert_eq!(
+            expected,
+            Poseidon::new_with_preimage(&preimage, &constants).hash()
+        );
+    }
+}
@@ -0,0 +1,90 @@
+//! Hash chains for running commitments.
+//!
+//! [`update_commitment`] folds one new element into a running commitment with a single two-input compression. This
+//! is a Merkle-Damgård-style chain, not a true incremental hash: the commitment to a sequence depends on the order of
+//! its elements, and can only be recomputed by replaying the chain from its start.
+use crate::hash_type::{CType, HashType};
+use crate::poseidon::{Poseidon, PoseidonConstants};
+use crate::{Strength, DEFAULT_STRENGTH};
+use ff::PrimeField;
+use generic_array::typenum::U2;
+
+/// Identifier of the custom [`HashType`] used for commitment updates. Custom hash types with this identifier should
+/// not be used for other purposes.
+pub const UPDATE_CUSTOM_ID: u64 = 256;
+
+/// The hash type of commitment updates.
+pub fn update_hash_type<F: PrimeField>() -> HashType<F, U2> {
+    HashType::Custom(CType::Arbitrary(UPDATE_CUSTOM_ID))
+}
+
+/// Constants for [`update_commitment`].
+pub fn update_constants<F: PrimeField>() -> PoseidonConstants<F, U2> {
+    update_constants_with_strength(DEFAULT_STRENGTH)
+}
+
+pub fn update_constants_with_strength<F: PrimeField>(
+    strength: Strength,
+) -> PoseidonConstants<F, U2> {
+    PoseidonConstants::new_with_strength_and_type(strength, update_hash_type())
+}
+
+/// Returns the commitment which results from appending `new_element` to the sequence committed to by `prev`.
+///
+/// # Panics
+///
+/// Panics if `constants` were not created by [`update_constants`] or [`update_constants_with_strength`], since
+/// updates would then not be domain-separated from other hashes.
+pub fn update_commitment<F: PrimeField>(
+    constants: &PoseidonConstants<F, U2>,
+    prev: F,
+    new_element: F,
+) -> F {
+    assert!(
+        constants.hash_type == update_hash_type(),
+        "commitment updates require update constants"
+    );
+    Poseidon::new_with_preimage(&[prev, new_element], constants).hash()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle::compress;
+    use blstrs::Scalar as Fr;
+    use ff::Field;
+
+    fn chain(constants: &PoseidonConstants<Fr, U2>, elements: &[Fr]) -> Fr {
+        elements.iter().fold(Fr::ZERO, |commitment, element| {
+            update_commitment(constants, commitment, *element)
+        })
+    }
+
+    #[test]
+    fn chain_is_deterministic_and_order_sensitive() {
+        let constants = update_constants::<Fr>();
+        let elements: Vec<Fr> = (1..=10u64).map(Fr::from).collect();
+
+        let commitment = chain(&constants, &elements);
+        assert_eq!(commitment, chain(&constants, &elements));
+        assert_ne!(commitment, chain(&constants, &elements[..9]));
+
+        let mut swapped = elements.clone();
+        swapped.swap(3, 4);
+        assert_ne!(commitment, chain(&constants, &swapped));
+
+        // Updates are domain-separated from Merkle tree compression of the same inputs.
+        let merkle_constants = PoseidonConstants::<Fr, U2>::new();
+        assert_ne!(
+            update_commitment(&constants, Fr::ZERO, elements[0]),
+            compress(&merkle_constants, Fr::ZERO, elements[0])
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "commitment updates require update constants")]
+    fn update_rejects_other_constants() {
+        let constants = PoseidonConstants::<Fr, U2>::new();
+        update_commitment(&constants, Fr::ZERO, Fr::ONE);
+    }
+}
@@ -0,0 +1,89 @@
+//! Fiat-Shamir challenge generation.
+//!
+//! [`hash_to_challenges`] absorbs a transcript of field elements into a sponge and squeezes the requested number of
+//! challenges, each a full (uniformly distributed) field element. It uses the [`SpongeAPI`] with the
+//! [`CHALLENGE_DOMAIN_SEPARATOR`], so challenges never coincide with digests of the same transcript computed for other
+//! purposes.
+use crate::poseidon::{Arity, PoseidonConstants};
+use crate::sponge::api::{IOPattern, SpongeAPI, SpongeOp};
+use crate::sponge::vanilla::{Mode, Sponge, SpongeTrait};
+use ff::PrimeField;
+
+/// Domain separator of challenge generation: the ASCII bytes `chal`.
+pub const CHALLENGE_DOMAIN_SEPARATOR: u32 = u32::from_be_bytes(*b"chal");
+
+/// Absorbs `transcript` and squeezes `n` challenges. The IO pattern (absorb the transcript length, squeeze `n`)
+/// determines the sponge's initial capacity element, so transcripts of different lengths, or requests for different
+/// numbers of challenges, are domain-separated. The result is deterministic in its arguments.
+///
+/// `constants` are typically obtained from `Sponge::api_constants`, as for any use of the [`SpongeAPI`].
+///
+/// # Panics
+///
+/// Panics if the length of `transcript` or `n` exceeds `u32::MAX >> 1`, the largest length of a sponge operation.
+pub fn hash_to_challenges<F, A>(
+    constants: &PoseidonConstants<F, A>,
+    transcript: &[F],
+    n: usize,
+) -> Vec<F>
+where
+    F: PrimeField,
+    A: Arity<F>,
+{
+    let absorb_len = u32::try_from(transcript.len()).expect("transcript too long");
+    let squeeze_len = u32::try_from(n).expect("too many challenges");
+    let pattern = IOPattern(vec![
+        SpongeOp::Absorb(absorb_len),
+        SpongeOp::Squeeze(squeeze_len),
+    ]);
+
+    let mut sponge = Sponge::new_with_constants(constants, Mode::Simplex);
+    let acc = &mut ();
+    sponge.start(pattern, Some(CHALLENGE_DOMAIN_SEPARATOR), acc);
+    SpongeAPI::absorb(&mut sponge, absorb_len, transcript, acc);
+    let challenges = SpongeAPI::squeeze(&mut sponge, squeeze_len, acc);
+    sponge
+        .finish(acc)
+        .expect("the IO pattern matches the operations performed");
+
+    challenges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poseidon::Poseidon;
+    use crate::Strength;
+    use blstrs::Scalar as Fr;
+    use generic_array::typenum::U4;
+
+    #[test]
+    fn challenges_are_deterministic_and_separated() {
+        let constants = Sponge::<Fr, U4>::api_constants(Strength::Standard);
+        let transcript: Vec<Fr> = (0..4u64).map(Fr::from).collect();
+
+        let challenges = hash_to_challenges(&constants, &transcript, 6);
+        assert_eq!(6, challenges.len());
+        assert_eq!(challenges, hash_to_challenges(&constants, &transcript, 6));
+        for (i, a) in challenges.iter().enumerate() {
+            assert!(challenges[i + 1..].iter().all(|b| a != b));
+        }
+
+        // Asking for a different number of challenges changes the IO pattern, and therefore all challenges.
+        let fewer = hash_to_challenges(&constants, &transcript, 2);
+        assert_ne!(fewer[..], challenges[..2]);
+
+        let mut other_transcript = transcript.clone();
+        other_transcript[3] = Fr::from(4);
+        assert_ne!(
+            challenges,
+            hash_to_challenges(&constants, &other_transcript, 6)
+        );
+
+        let digest = Poseidon::new_with_preimage(&transcript, &constants).hash();
+        assert!(!challenges.contains(&digest));
+        let merkle_constants = PoseidonConstants::<Fr, U4>::new();
+        let merkle_digest = Poseidon::new_with_preimage(&transcript, &merkle_constants).hash();
+        assert!(!challenges.contains(&merkle_digest));
+    }
+}
@@ -0,0 +1,183 @@
+//! Interop with the Poseidon parameters published by [circomlib](https://github.com/iden3/circomlib).
+//!
+//! circomlibjs ships its parameters as a JSON document (`poseidon_constants.json`) with one entry per width
+//! `t = arity + 1`, starting at `t = 2`. Each entry of `C` holds the flat list of round constants and each entry of
+//! `M` holds the MDS matrix, with all field elements encoded as big-endian hex strings. [`CircomlibConstants`]
+//! deserializes that format and converts an entry to [`PoseidonConstants`].
+//!
+//! Reproducing circomlib digests requires two adjustments, both handled by [`hash`]:
+//! - circomlib initializes the capacity element to zero, so the imported constants use [`HashType::Sponge`]
+//!   (whose domain tag is zero) rather than the `neptune` Merkle tree tag.
+//! - circomlib outputs the first element of the final state, while `neptune` outputs the second
+//!   (see [`Poseidon::extract_output`]).
+//!
+//! The round constants are used as published: circomlib adds them before each S-box, which matches the uncompressed
+//! `neptune` round constants. circomlib computes `mds * state`, so the matrix is transposed on import.
+use crate::hash_type::HashType;
+use crate::hex::scalar_from_hex;
+use crate::matrix::{transpose, Matrix};
+use crate::poseidon::{Arity, Poseidon, PoseidonConstants};
+use crate::{Error, Strength};
+use ff::PrimeField;
+use generic_array::typenum::Unsigned;
+use serde::Deserialize;
+
+/// Number of full rounds used by circomlib for every width.
+pub const CIRCOMLIB_FULL_ROUNDS: usize = 8;
+
+/// Poseidon parameters in the circomlibjs `poseidon_constants.json` format. Other fields of that document (the
+/// optimized `S` and `P` constants) are ignored, since `neptune` derives its own optimized constants.
+#[derive(Clone, Debug, Deserialize)]
+pub struct CircomlibConstants {
+    /// Round constants, indexed by `t - 2`.
+    #[serde(rename = "C")]
+    pub c: Vec<Vec<String>>,
+    /// MDS matrices, indexed by `t - 2`.
+    #[serde(rename = "M")]
+    pub m: Vec<Vec<Vec<String>>>,
+}
+
+impl CircomlibConstants {
+    /// Converts the entry for width [`Arity`] + 1 to [`PoseidonConstants`]. The number of partial rounds is derived
+    /// from the number of round constants, since circomlib always uses [`CIRCOMLIB_FULL_ROUNDS`] full rounds.
+    pub fn to_poseidon_constants<F, A>(&self) -> Result<PoseidonConstants<F, A>, Error>
+    where
+        F: PrimeField,
+        A: Arity<F>,
+    {
+        let width = A::ConstantsSize::to_usize();
+        let index = width
+            .checked_sub(2)
+            .ok_or_else(|| Error::Other("circomlib does not support arity 0".to_string()))?;
+
+        let c = self
+            .c
+            .get(index)
+            .ok_or_else(|| Error::Other(format!("no circomlib round constants for t = {width}")))?;
+        let m = self
+            .m
+            .get(index)
+            .ok_or_else(|| Error::Other(format!("no circomlib MDS matrix for t = {width}")))?;
+
+        if c.len() % width != 0 || c.len() / width <= CIRCOMLIB_FULL_ROUNDS {
+            return Err(Error::Other(format!(
+                "invalid number of circomlib round constants ({}) for t = {width}",
+                c.len()
+            )));
+        }
+        let partial_rounds = c.len() / width - CIRCOMLIB_FULL_ROUNDS;
+
+        if m.len() != width || m.iter().any(|row| row.len() != width) {
+            return Err(Error::Other(format!(
+                "circomlib MDS matrix is not of size {width}x{width}"
+            )));
+        }
+
+        let round_constants = c
+            .iter()
+            .map(|s| scalar_from_hex(s))
+            .collect::<Result<Vec<F>, Error>>()?;
+        let mds: Matrix<F> = m
+            .iter()
+            .map(|row| row.iter().map(|s| scalar_from_hex(s)).collect())
+            .collect::<Result<_, Error>>()?;
+
+        Ok(PoseidonConstants::from_parts(
+            transpose(&mds),
+            round_constants,
+            CIRCOMLIB_FULL_ROUNDS,
+            partial_rounds,
+            Strength::Standard,
+            HashType::Sponge,
+        ))
+    }
+}
+
+/// Hashes `preimage` the way circomlib does: the capacity element starts at zero and the digest is the first element
+/// of the final state. `constants` should come from [`CircomlibConstants::to_poseidon_constants`].
+pub fn hash<F, A>(constants: &PoseidonConstants<F, A>, preimage: &[F]) -> F
+where
+    F: PrimeField,
+    A: Arity<F>,
+{
+    let mut p = Poseidon::new_with_preimage(preimage, constants);
+    p.hash();
+    p.elements[0]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hex::scalar_to_hex;
+    use blstrs::Scalar as Fr;
+    use generic_array::typenum::U2;
+
+    #[test]
+    fn import_roundtrip() {
+        // Export `neptune`'s own parameters in the circomlib format and import them again.
+        let constants = PoseidonConstants::<Fr, U2>::new_with_strength_and_type(
+            Strength::Standard,
+            HashType::Sponge,
+        );
+        let c: Vec<String> = constants
+            .round_constants
+            .as_ref()
+            .unwrap()
+            .iter()
+            .take(constants.width() * (constants.full_rounds + constants.partial_rounds))
+            .map(scalar_to_hex)
+            .collect();
+        let m: Vec<Vec<String>> = transpose(&constants.mds_matrices.m)
+            .iter()
+            .map(|row| row.iter().map(scalar_to_hex).collect())
+            .collect();
+        let json = serde_json::json!({ "C": [[], c], "M": [[], m], "P": [], "S": [] });
+
+        let circomlib: CircomlibConstants = serde_json::from_value(json).unwrap();
+        let imported = circomlib.to_poseidon_constants::<Fr, U2>().unwrap();
+
+        assert_eq!(constants.full_rounds, imported.full_rounds);
+        assert_eq!(constants.partial_rounds, imported.partial_rounds);
+        assert_eq!(constants.mds_matrices, imported.mds_matrices);
+        assert_eq!(
+            constants.compressed_round_constants,
+            imported.compressed_round_constants
+        );
+
+        let preimage = [Fr::from(1), Fr::from(2)];
+        let mut p = Poseidon::new_with_preimage(&preimage, &constants);
+        p.hash();
+        assert_eq!(p.elements[0], hash(&imported, &preimage));
+
+        // Arities without an entry are rejected.
+        assert!(circomlib
+            .to_poseidon_constants::<Fr, generic_array::typenum::U4>()
+            .is_err());
+    }
+
+    #[cfg(feature = "bn254")]
+    #[test]
+    fn circomlib_test_vector() {
+        use halo2curves::bn256::Fr as B;
+
+        // The `t = 3` entry of circomlibjs's `poseidon_constants.json`.
+        let t3: serde_json::Value =
+            serde_json::from_str(include_str!("../parameters/circomlib-poseidon-t3.json")).unwrap();
+        let json = serde_json::json!({ "C": [[], t3["C"]], "M": [[], t3["M"]] });
+        let circomlib: CircomlibConstants = serde_json::from_value(json).unwrap();
+        let constants = circomlib.to_poseidon_constants::<B, U2>().unwrap();
+        assert_eq!(
+            (CIRCOMLIB_FULL_ROUNDS, 57),
+            (constants.full_rounds, constants.partial_rounds)
+        );
+
+        // circomlib's Poseidon([1, 2]).
+        assert_eq!(
+            scalar_from_hex::<B>(
+                "0x115cc0f5e7d690413df64c6b9662e9cf2a3617f2743245519e19607a4417189a"
+            )
+            .unwrap(),
+            hash(&constants, &[B::from(1u64), B::from(2u64)])
+        );
+    }
+}
@@ -760,6 +760,10 @@ mod tests {
                 (width * s_box_cost * constants.full_rounds)
                     + (s_box_cost * constants.partial_rounds)
             };
+            assert_eq!(
+                expected_constraints_calculated,
+                constants.estimate_constraints()
+            );
 
             let mut data = |cs: &mut TestConstraintSystem<Fr>, fr_data: &mut [Fr]| {
                 (0..preimage_length)
@@ -1,5 +1,6 @@
 use crate::batch_hasher::Batcher;
 use crate::error::Error;
+use crate::multi_arity::{ArityConstants, MultiArityConstants};
 use crate::poseidon::{Poseidon, PoseidonConstants};
 use crate::tree_builder::{TreeBuilder, TreeBuilderTrait};
 use crate::{Arity, BatchHasher, NeptuneField};
@@ -127,6 +128,45 @@ where
         Ok(builder)
     }
 
+    /// Like [`ColumnTreeBuilder::new`], but takes the column and tree constants from `constants` instead of
+    /// generating them. Returns [`Error::Other`] if `constants` lacks either arity, as for constants generated for a
+    /// different [`TreeShape`](crate::multi_arity::TreeShape).
+    pub fn new_with_constants(
+        column_batcher: Option<Batcher<F, ColumnArity>>,
+        tree_batcher: Option<Batcher<F, TreeArity>>,
+        leaf_count: usize,
+        constants: &MultiArityConstants<F>,
+    ) -> Result<Self, Error>
+    where
+        MultiArityConstants<F>: ArityConstants<F, ColumnArity> + ArityConstants<F, TreeArity>,
+    {
+        let missing = |arity: usize| Error::Other(format!("no constants for arity {arity}"));
+        let tree_constants = constants
+            .try_get::<TreeArity>()
+            .ok_or_else(|| missing(TreeArity::to_usize()))?;
+        let column_constants = constants
+            .try_get::<ColumnArity>()
+            .ok_or_else(|| missing(ColumnArity::to_usize()))?;
+
+        let tree_builder = TreeBuilder::<F, TreeArity>::new_with_constants(
+            tree_batcher,
+            leaf_count,
+            0,
+            tree_constants.clone(),
+        )?;
+
+        let builder = Self {
+            leaf_count,
+            data: vec![F::ZERO; leaf_count],
+            fill_index: 0,
+            column_constants: column_constants.clone(),
+            column_batcher,
+            tree_builder,
+        };
+
+        Ok(builder)
+    }
+
     pub fn tree_size(&self) -> usize {
         self.tree_builder.tree_size(0)
     }
@@ -178,6 +218,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_column_tree_builder_with_constants() {
+        let constants = MultiArityConstants::<Fr>::new();
+        let leaves = 512;
+        let columns: Vec<GenericArray<Fr, U11>> = (0..leaves)
+            .map(|i| GenericArray::generate(|j| Fr::from((i * 11 + j) as u64)))
+            .collect();
+
+        let mut generated = ColumnTreeBuilder::<Fr, U11, U8>::new(None, None, leaves).unwrap();
+        let mut shared =
+            ColumnTreeBuilder::<Fr, U11, U8>::new_with_constants(None, None, leaves, &constants)
+                .unwrap();
+
+        assert_eq!(
+            generated.add_final_columns(&columns).unwrap(),
+            shared.add_final_columns(&columns).unwrap()
+        );
+    }
+
     fn test_column_tree_builder_aux(
         column_batcher: Option<Batcher<Fr, U11>>,
         tree_batcher: Option<Batcher<Fr, U8>>,
@@ -0,0 +1,133 @@
+//! Nullifiers and commitments for privacy protocols.
+//!
+//! [`nullifier`] and [`commitment`] are each a single two-input hash, with their own custom [`HashType`]. Their
+//! domain tags differ from each other and from those of Merkle trees, encryption and the other hash types, so a
+//! nullifier can never be mistaken for a commitment, or either for a Merkle node, even for the same inputs.
+use crate::hash_type::{CType, HashType};
+use crate::poseidon::{Poseidon, PoseidonConstants};
+use crate::{Strength, DEFAULT_STRENGTH};
+use ff::PrimeField;
+use generic_array::typenum::U2;
+
+/// Identifier of the custom [`HashType`] used for nullifiers. Custom hash types with this identifier should not be
+/// used for other purposes.
+pub const NULLIFIER_CUSTOM_ID: u64 = 255;
+
+/// Identifier of the custom [`HashType`] used for commitments. Custom hash types with this identifier should not be
+/// used for other purposes.
+pub const COMMITMENT_CUSTOM_ID: u64 = 254;
+
+/// The hash type of nullifiers.
+pub fn nullifier_hash_type<F: PrimeField>() -> HashType<F, U2> {
+    HashType::Custom(CType::Arbitrary(NULLIFIER_CUSTOM_ID))
+}
+
+/// The hash type of commitments.
+pub fn commitment_hash_type<F: PrimeField>() -> HashType<F, U2> {
+    HashType::Custom(CType::Arbitrary(COMMITMENT_CUSTOM_ID))
+}
+
+/// Constants for [`nullifier`].
+pub fn nullifier_constants<F: PrimeField>() -> PoseidonConstants<F, U2> {
+    nullifier_constants_with_strength(DEFAULT_STRENGTH)
+}
+
+pub fn nullifier_constants_with_strength<F: PrimeField>(
+    strength: Strength,
+) -> PoseidonConstants<F, U2> {
+    PoseidonConstants::new_with_strength_and_type(strength, nullifier_hash_type())
+}
+
+/// Constants for [`commitment`].
+pub fn commitment_constants<F: PrimeField>() -> PoseidonConstants<F, U2> {
+    commitment_constants_with_strength(DEFAULT_STRENGTH)
+}
+
+pub fn commitment_constants_with_strength<F: PrimeField>(
+    strength: Strength,
+) -> PoseidonConstants<F, U2> {
+    PoseidonConstants::new_with_strength_and_type(strength, commitment_hash_type())
+}
+
+/// Returns the nullifier of the note at `index` owned by `secret`, the hash of `secret` and `index`.
+///
+/// # Panics
+///
+/// Panics if `constants` were not created by [`nullifier_constants`] or [`nullifier_constants_with_strength`].
+pub fn nullifier<F: PrimeField>(constants: &PoseidonConstants<F, U2>, secret: F, index: u64) -> F {
+    assert!(
+        constants.hash_type == nullifier_hash_type(),
+        "nullifiers require nullifier constants"
+    );
+    Poseidon::new_with_preimage(&[secret, F::from(index)], constants).hash()
+}
+
+/// Returns the commitment to `value` with blinding factor `randomness`, the hash of `value` and `randomness`.
+/// `randomness` must be sampled uniformly for the commitment to hide `value`.
+///
+/// # Panics
+///
+/// Panics if `constants` were not created by [`commitment_constants`] or [`commitment_constants_with_strength`].
+pub fn commitment<F: PrimeField>(
+    constants: &PoseidonConstants<F, U2>,
+    value: F,
+    randomness: F,
+) -> F {
+    assert!(
+        constants.hash_type == commitment_hash_type(),
+        "commitments require commitment constants"
+    );
+    Poseidon::new_with_preimage(&[value, randomness], constants).hash()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::{update_commitment, update_constants};
+    use crate::merkle::compress;
+    use blstrs::Scalar as Fr;
+    use ff::Field;
+
+    #[test]
+    fn domains_are_separated() {
+        let nullifier_constants = nullifier_constants::<Fr>();
+        let commitment_constants = commitment_constants::<Fr>();
+        let (a, b) = (Fr::from(3), Fr::from(5));
+
+        let nullifier = nullifier(&nullifier_constants, a, 5);
+        assert_eq!(nullifier, super::nullifier(&nullifier_constants, a, 5));
+        assert_ne!(nullifier, super::nullifier(&nullifier_constants, a, 6));
+
+        let commitment = commitment(&commitment_constants, a, b);
+        assert_eq!(commitment, super::commitment(&commitment_constants, a, b));
+
+        let others = [
+            commitment,
+            compress(&PoseidonConstants::<Fr, U2>::new(), a, b),
+            update_commitment(&update_constants(), a, b),
+            Poseidon::new_with_preimage(
+                &[a, b],
+                &PoseidonConstants::<Fr, U2>::new_constant_length(2),
+            )
+            .hash(),
+        ];
+        for other in &others[1..] {
+            assert_ne!(commitment, *other);
+        }
+        for other in &others {
+            assert_ne!(nullifier, *other);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "nullifiers require nullifier constants")]
+    fn nullifier_rejects_commitment_constants() {
+        nullifier(&commitment_constants::<Fr>(), Fr::ONE, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "commitments require commitment constants")]
+    fn commitment_rejects_nullifier_constants() {
+        commitment(&nullifier_constants::<Fr>(), Fr::ONE, Fr::ONE);
+    }
+}
@@ -0,0 +1,83 @@
+//! Digest compatibility with the upstream `neptune` release this crate is versioned after.
+//!
+//! With default features, digests are identical to upstream for every supported hash type and strength. The only
+//! intentional divergence is the insecure `Strength::Fast` of the `insecure-fast` feature, which does not exist upstream
+//! and whose domain tags deliberately differ from all upstream tags.
+use crate::hash_type::{CType, HashType};
+use crate::poseidon::{Arity, Poseidon, PoseidonConstants};
+use crate::Strength;
+use blstrs::Scalar as Fr;
+use generic_array::typenum::{Unsigned, U2, U4, U8};
+use neptune_upstream::hash_type::{CType as UpstreamCType, HashType as UpstreamHashType};
+use neptune_upstream::poseidon::{
+    Poseidon as UpstreamPoseidon, PoseidonConstants as UpstreamConstants,
+};
+use neptune_upstream::Strength as UpstreamStrength;
+
+fn check<A>(strength: Strength, upstream_strength: UpstreamStrength)
+where
+    A: Arity<Fr> + neptune_upstream::Arity<Fr>,
+{
+    let preimage: Vec<Fr> = (0..A::to_usize() as u64).map(Fr::from).collect();
+
+    let constants = PoseidonConstants::<Fr, A>::new_with_strength(strength);
+    let upstream = UpstreamConstants::<Fr, A>::new_with_strength(upstream_strength);
+    assert_eq!(
+        Poseidon::new_with_preimage(&preimage, &constants).hash(),
+        UpstreamPoseidon::new_with_preimage(&preimage, &upstream).hash(),
+        "merkle tree digests differ for arity {}",
+        A::to_usize()
+    );
+
+    let length = A::to_usize() - 1;
+    let constants = PoseidonConstants::<Fr, A>::new_constant_length(length);
+    let upstream = UpstreamConstants::<Fr, A>::new_with_strength_and_type(
+        UpstreamStrength::Standard,
+        UpstreamHashType::ConstantLength(length),
+    );
+    assert_eq!(
+        Poseidon::new_with_preimage(&preimage[..length], &constants).hash(),
+        UpstreamPoseidon::new_with_preimage(&preimage[..length], &upstream).hash(),
+        "constant-length digests differ for arity {}",
+        A::to_usize()
+    );
+
+    for (hash_type, upstream_hash_type) in [
+        (HashType::Encryption, UpstreamHashType::Encryption),
+        (
+            HashType::Custom(CType::Arbitrary(1)),
+            UpstreamHashType::Custom(UpstreamCType::Arbitrary(1)),
+        ),
+        (
+            HashType::Custom(CType::Arbitrary(256)),
+            UpstreamHashType::Custom(UpstreamCType::Arbitrary(256)),
+        ),
+        (HashType::Sponge, UpstreamHashType::Sponge),
+    ] {
+        let name = format!("{:?}", hash_type);
+        let constants = PoseidonConstants::<Fr, A>::new_with_strength_and_type(strength, hash_type);
+        let upstream = UpstreamConstants::<Fr, A>::new_with_strength_and_type(
+            upstream_strength,
+            upstream_hash_type,
+        );
+        assert_eq!(
+            Poseidon::new_with_preimage(&preimage, &constants).hash(),
+            UpstreamPoseidon::new_with_preimage(&preimage, &upstream).hash(),
+            "{} digests differ for arity {}",
+            name,
+            A::to_usize()
+        );
+    }
+}
+
+#[test]
+fn digests_match_upstream() {
+    for (strength, upstream_strength) in [
+        (Strength::Standard, UpstreamStrength::Standard),
+        (Strength::Strengthened, UpstreamStrength::Strengthened),
+    ] {
+        check::<U2>(strength, upstream_strength);
+        check::<U4>(strength, upstream_strength);
+        check::<U8>(strength, upstream_strength);
+    }
+}
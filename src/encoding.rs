@@ -0,0 +1,176 @@
+//! Hashing of structured values.
+//!
+//! Types implementing [`ToFieldElements`] describe how their values map to field elements, and [`hash_encodable`]
+//! hashes the resulting elements as a constant-length preimage. Every implementation in this module encodes all
+//! values of a type as the same number of elements, so the encoding of a tuple, the concatenation of the encodings of
+//! its members, is injective for a given tuple type. Values of different types may share an encoding (`1u64` and
+//! `true` both encode as one), so the type of hashed values must be fixed by the context of the hash.
+//!
+//! Sequences of variable-length parts are hashed by [`hash_length_prefixed`], which prefixes each part with its length
+//! so that the boundaries between parts are part of the encoding.
+use crate::poseidon::{hash_exact, Arity, PoseidonConstants};
+use ff::PrimeField;
+
+/// Conversion of a value to field elements.
+pub trait ToFieldElements<F: PrimeField> {
+    /// Appends the encoding of `self` to `elements`.
+    fn append_field_elements(&self, elements: &mut Vec<F>);
+
+    /// Returns the encoding of `self`.
+    fn to_field_elements(&self) -> Vec<F> {
+        let mut elements = Vec::new();
+        self.append_field_elements(&mut elements);
+        elements
+    }
+}
+
+impl<F: PrimeField> ToFieldElements<F> for u64 {
+    fn append_field_elements(&self, elements: &mut Vec<F>) {
+        elements.push(F::from(*self));
+    }
+}
+
+impl<F: PrimeField> ToFieldElements<F> for bool {
+    fn append_field_elements(&self, elements: &mut Vec<F>) {
+        elements.push(F::from(u64::from(*self)));
+    }
+}
+
+// Field elements encode as themselves. A blanket implementation for all `F: PrimeField` would overlap with the
+// implementations above, so the supported fields are listed.
+macro_rules! impl_to_field_elements_for_field {
+    ($($(#[$attr:meta])* $field:ty),*) => {
+        $(
+            $(#[$attr])*
+            impl ToFieldElements<$field> for $field {
+                fn append_field_elements(&self, elements: &mut Vec<$field>) {
+                    elements.push(*self);
+                }
+            }
+        )*
+    };
+}
+
+impl_to_field_elements_for_field!(
+    #[cfg(any(test, feature = "blstrs"))]
+    blstrs::Scalar,
+    pasta_curves::Fp,
+    pasta_curves::Fq
+);
+
+macro_rules! impl_to_field_elements_for_tuple {
+    ($($t:ident: $i:tt),+) => {
+        impl<F: PrimeField, $($t: ToFieldElements<F>),+> ToFieldElements<F> for ($($t,)+) {
+            fn append_field_elements(&self, elements: &mut Vec<F>) {
+                $(self.$i.append_field_elements(elements);)+
+            }
+        }
+    };
+}
+
+impl_to_field_elements_for_tuple!(T0: 0);
+impl_to_field_elements_for_tuple!(T0: 0, T1: 1);
+impl_to_field_elements_for_tuple!(T0: 0, T1: 1, T2: 2);
+impl_to_field_elements_for_tuple!(T0: 0, T1: 1, T2: 2, T3: 3);
+impl_to_field_elements_for_tuple!(T0: 0, T1: 1, T2: 2, T3: 3, T4: 4);
+impl_to_field_elements_for_tuple!(T0: 0, T1: 1, T2: 2, T3: 3, T4: 4, T5: 5);
+
+/// Hashes the encoding of `value` as a constant-length preimage, exactly as [`hash_exact`] hashes the elements of
+/// [`ToFieldElements::to_field_elements`].
+pub fn hash_encodable<F, A, T>(constants: &PoseidonConstants<F, A>, value: &T) -> F
+where
+    F: PrimeField,
+    A: Arity<F>,
+    T: ToFieldElements<F> + ?Sized,
+{
+    hash_exact(constants, value.to_field_elements().into_iter())
+        .expect("encoded elements have the reported length")
+}
+
+/// Hashes a sequence of variable-length parts. Each part is encoded as its length, as a field element, followed by its
+/// elements, and the concatenated encodings are hashed as a constant-length preimage with [`hash_exact`]. For
+/// example, `[[a], [b, c]]` is encoded as `[1, a, 2, b, c]` and `[[a, b], [c]]` as `[2, a, b, 1, c]`. The encoding is
+/// injective for sequences of parts, so moving elements across part boundaries, or adding empty parts, changes the
+/// digest.
+pub fn hash_length_prefixed<F, A>(constants: &PoseidonConstants<F, A>, parts: &[&[F]]) -> F
+where
+    F: PrimeField,
+    A: Arity<F>,
+{
+    let encoding: Vec<F> = parts
+        .iter()
+        .flat_map(|part| std::iter::once(F::from(part.len() as u64)).chain(part.iter().copied()))
+        .collect();
+    hash_exact(constants, encoding.into_iter()).expect("encoded elements have the reported length")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blstrs::Scalar as Fr;
+    use ff::Field;
+    use generic_array::typenum::U4;
+
+    #[test]
+    fn tuple_encoding() {
+        let constants = PoseidonConstants::<Fr, U4>::new();
+        let x = Fr::from(123456789);
+        let value = (7u64, true, x);
+
+        assert_eq!(
+            vec![Fr::from(7), Fr::ONE, x],
+            ToFieldElements::<Fr>::to_field_elements(&value)
+        );
+        assert_eq!(
+            hash_exact(&constants, [Fr::from(7), Fr::ONE, x].into_iter()).unwrap(),
+            hash_encodable(&constants, &value)
+        );
+        assert_eq!(
+            hash_encodable(&constants, &value),
+            hash_encodable(&constants, &value)
+        );
+        assert_ne!(
+            hash_encodable(&constants, &value),
+            hash_encodable(&constants, &(7u64, false, x))
+        );
+
+        // Nested tuples flatten; longer preimages are absorbed by a sponge.
+        let nested = ((1u64, 2u64), (false, x), (3u64,));
+        assert_eq!(
+            vec![Fr::from(1), Fr::from(2), Fr::ZERO, x, Fr::from(3)],
+            ToFieldElements::<Fr>::to_field_elements(&nested)
+        );
+        assert_eq!(
+            hash_exact(
+                &constants,
+                [Fr::from(1), Fr::from(2), Fr::ZERO, x, Fr::from(3)].into_iter()
+            )
+            .unwrap(),
+            hash_encodable(&constants, &nested)
+        );
+    }
+
+    #[test]
+    fn length_prefixes_disambiguate() {
+        let constants = PoseidonConstants::<Fr, U4>::new();
+        let (a, b, c) = (Fr::from(1), Fr::from(2), Fr::from(3));
+
+        let digest = hash_length_prefixed(&constants, &[&[a], &[b, c]]);
+        assert_eq!(
+            hash_exact(&constants, [Fr::ONE, a, Fr::from(2), b, c].into_iter()).unwrap(),
+            digest
+        );
+        assert_eq!(digest, hash_length_prefixed(&constants, &[&[a], &[b, c]]));
+
+        let others: [&[&[Fr]]; 5] = [
+            &[&[a, b], &[c]],
+            &[&[a, b, c]],
+            &[&[a], &[b], &[c]],
+            &[&[a], &[b, c], &[]],
+            &[&[], &[a], &[b, c]],
+        ];
+        for other in others {
+            assert_ne!(digest, hash_length_prefixed(&constants, other));
+        }
+    }
+}
@@ -47,6 +47,35 @@ pub enum Error {
     FullBuffer,
     /// Attempt to reference an index element that is out of bounds
     IndexOutOfBounds,
+    /// The provided preimage length does not match the arity.
+    InvalidPreimageLength {
+        got: usize,
+        expected: usize,
+    },
+    /// The round constants failed a sanity check.
+    InvalidRoundConstants(String),
+    /// There are fewer round constants than the round schedule requires.
+    InsufficientRoundConstants {
+        got: usize,
+        expected: usize,
+    },
+    /// Reading or writing encoded constants failed.
+    Io(String),
+    /// Encoded constants are malformed.
+    Format(String),
+    /// Encoded constants were written for a different field.
+    FieldMismatch,
+    /// Encoded constants were written in a newer format version than this version of the crate reads.
+    UnsupportedVersion {
+        found: u16,
+        max_supported: u16,
+    },
+    /// No MDS matrix, or no sparse factorization of it, could be generated for the field and width.
+    MdsGenerationFailed(String),
+    /// The arity exceeds the largest supported for the field, see [`crate::poseidon::max_arity`].
+    ArityTooLarge {
+        max: usize,
+    },
     GpuError(String),
     #[cfg(any(feature = "cuda", feature = "opencl"))]
     ClError(ClError),
@@ -77,6 +106,29 @@ impl fmt::Display for Error {
                 "The size of the buffer cannot be greater than the hash arity."
             ),
             Error::IndexOutOfBounds => write!(f, "The referenced index is outs of bounds."),
+            Error::InvalidPreimageLength { got, expected } => write!(
+                f,
+                "The preimage length ({got}) does not match the arity ({expected})."
+            ),
+            Error::InvalidRoundConstants(s) => write!(f, "Invalid round constants: {s}"),
+            Error::InsufficientRoundConstants { got, expected } => write!(
+                f,
+                "Insufficient round constants: got {got}, the round schedule requires {expected}."
+            ),
+            Error::Io(s) => write!(f, "IO Error: {s}"),
+            Error::Format(s) => write!(f, "Invalid format: {s}"),
+            Error::FieldMismatch => write!(f, "The constants were encoded for a different field."),
+            Error::UnsupportedVersion {
+                found,
+                max_supported,
+            } => write!(
+                f,
+                "Unsupported format version {found}, the latest supported is {max_supported}."
+            ),
+            Error::MdsGenerationFailed(s) => write!(f, "MDS generation failed: {s}"),
+            Error::ArityTooLarge { max } => {
+                write!(f, "The arity exceeds the supported maximum of {max}.")
+            }
             Error::GpuError(s) => write!(f, "GPU Error: {s}"),
             #[cfg(any(feature = "cuda", feature = "opencl"))]
             Error::ClError(e) => write!(f, "OpenCL Error: {e}"),
@@ -13,8 +13,9 @@
 use crate::{Arity, Strength};
 use ff::PrimeField;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 #[serde(bound(
     serialize = "F: PrimeField + Serialize, A: Arity<F>",
     deserialize = "F: PrimeField + Deserialize<'de>, A: Arity<F>"
@@ -55,6 +56,17 @@ impl<F: PrimeField, A: Arity<F>> HashType<F, A> {
         }
     }
 
+    /// Returns the domain tag used by constants of the given [`Strength`]. This equals [`HashType::domain_tag`] for
+    /// secure strengths. For the insecure `Strength::Fast`, 2^192 is added, which exceeds all other tags, so that its
+    /// digests are never mistaken for those of a secure hash.
+    pub fn domain_tag_with_strength(&self, strength: Strength) -> F {
+        match strength {
+            Strength::Standard | Strength::Strengthened => self.domain_tag(),
+            #[cfg(feature = "insecure-fast")]
+            Strength::Fast => self.domain_tag() + pow2::<F>(192),
+        }
+    }
+
     /// Some HashTypes require more testing so are not yet supported, since they are not yet needed.
     /// As and when needed, support can be added, along with tests to ensure the initial implementation
     /// is sound.
@@ -71,7 +83,7 @@ impl<F: PrimeField, A: Arity<F>> HashType<F, A> {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub enum CType<F: PrimeField, A: Arity<F>> {
     Arbitrary(u64),
     // See: https://github.com/bincode-org/bincode/issues/424
@@ -82,6 +94,35 @@ pub enum CType<F: PrimeField, A: Arity<F>> {
     _Phantom((F, A)),
 }
 
+// `Debug` is implemented by hand, since deriving it would require `A: Debug`, which arities bounded only by `Arity`
+// do not satisfy.
+impl<F: PrimeField, A: Arity<F>> fmt::Debug for HashType<F, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HashType::MerkleTree => f.write_str("MerkleTree"),
+            HashType::MerkleTreeSparse(bitmask) => {
+                f.debug_tuple("MerkleTreeSparse").field(bitmask).finish()
+            }
+            HashType::VariableLength => f.write_str("VariableLength"),
+            HashType::ConstantLength(length) => {
+                f.debug_tuple("ConstantLength").field(length).finish()
+            }
+            HashType::Encryption => f.write_str("Encryption"),
+            HashType::Custom(ctype) => f.debug_tuple("Custom").field(ctype).finish(),
+            HashType::Sponge => f.write_str("Sponge"),
+        }
+    }
+}
+
+impl<F: PrimeField, A: Arity<F>> fmt::Debug for CType<F, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CType::Arbitrary(id) => f.debug_tuple("Arbitrary").field(id).finish(),
+            CType::_Phantom(_) => f.write_str("_Phantom"),
+        }
+    }
+}
+
 impl<F: PrimeField, A: Arity<F>> CType<F, A> {
     const fn identifier(&self) -> u64 {
         match self {
@@ -114,9 +155,10 @@ fn x_pow2<F: PrimeField>(coeff: u64, n: u64) -> F {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::poseidon::PoseidonConstants;
     use crate::{scalar_from_u64s, Strength};
     use blstrs::Scalar as Fr;
-    use generic_array::typenum::{U15, U8};
+    use generic_array::typenum::{U15, U4, U8};
     use std::collections::HashSet;
 
     #[test]
@@ -205,4 +247,28 @@ mod tests {
         // hence no tag is duplicated.
         assert_eq!(all_tags.len(), all_tags_set.len(), "duplicate tag produced");
     }
+
+    #[test]
+    fn test_distinct_tags_for_arity() {
+        let hash_types = [
+            HashType::<Fr, U4>::MerkleTree,
+            HashType::VariableLength,
+            HashType::Encryption,
+            HashType::ConstantLength(4),
+        ];
+        let tags: HashSet<_> = hash_types
+            .iter()
+            .map(|hash_type| hash_type.domain_tag().to_repr())
+            .collect();
+        assert_eq!(hash_types.len(), tags.len(), "duplicate tag produced");
+
+        // The constants carry the tag of their hash type into the capacity element.
+        for hash_type in hash_types.into_iter().filter(HashType::is_supported) {
+            let constants = PoseidonConstants::<Fr, U4>::new_with_strength_and_type(
+                Strength::Standard,
+                hash_type.clone(),
+            );
+            assert_eq!(hash_type.domain_tag(), constants.domain_tag);
+        }
+    }
 }
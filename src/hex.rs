@@ -0,0 +1,139 @@
+//! Hashing of hex-encoded field elements, for use from shell pipelines.
+//!
+//! Field elements are written as big-endian hex, optionally prefixed by `0x`, and separated by any whitespace.
+//! Digests are written in the same form, `0x`-prefixed and zero-padded to the full element size.
+use crate::bytes::{scalar_from_bytes_be, scalar_to_bytes_be};
+use crate::poseidon::{Arity, Poseidon, PoseidonConstants};
+use crate::Error;
+use ff::PrimeField;
+use std::io::{BufRead, Write};
+
+/// Parses a big-endian hex field element, with or without a `0x` prefix. Fewer digits than the element size are
+/// zero-padded on the left.
+pub fn scalar_from_hex<F: PrimeField>(hex: &str) -> Result<F, Error> {
+    let digits = hex.strip_prefix("0x").unwrap_or(hex);
+    let element_len = F::Repr::default().as_ref().len();
+    if digits.is_empty() || digits.len() > 2 * element_len {
+        return Err(Error::Format(format!(
+            "expected 1 to {} hex digits, got {}",
+            2 * element_len,
+            digits.len()
+        )));
+    }
+
+    let mut bytes = vec![0u8; element_len];
+    for (i, digit) in digits.bytes().rev().enumerate() {
+        let value = (digit as char)
+            .to_digit(16)
+            .ok_or_else(|| Error::Format(format!("invalid hex digit {:?}", digit as char)))?;
+        bytes[element_len - 1 - i / 2] |= (value as u8) << (4 * (i % 2));
+    }
+    scalar_from_bytes_be(&bytes)
+}
+
+/// Formats a field element as `0x`-prefixed big-endian hex.
+pub fn scalar_to_hex<F: PrimeField>(scalar: &F) -> String {
+    let digits: String = scalar_to_bytes_be(scalar)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    format!("0x{digits}")
+}
+
+/// Reads hex field elements from `reader` and writes the digest of each consecutive group of [`Arity`] elements to
+/// `writer`, one per line. Groups may span lines. Returns [`Error::Format`] for malformed elements, naming the line,
+/// or if the number of elements is not a multiple of the arity, and [`Error::Io`] if reading or writing fails.
+pub fn hash_hex_lines<F, A, R, W>(
+    constants: &PoseidonConstants<F, A>,
+    reader: R,
+    mut writer: W,
+) -> Result<(), Error>
+where
+    F: PrimeField,
+    A: Arity<F>,
+    R: BufRead,
+    W: Write,
+{
+    let arity = A::to_usize();
+    let mut poseidon = Poseidon::new(constants);
+    let mut group = Vec::with_capacity(arity);
+
+    for (line_number, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| Error::Io(e.to_string()))?;
+        for word in line.split_whitespace() {
+            let element = scalar_from_hex(word)
+                .map_err(|e| Error::Format(format!("line {}: {e}", line_number + 1)))?;
+            group.push(element);
+
+            if group.len() == arity {
+                poseidon.set_preimage(&group);
+                writeln!(writer, "{}", scalar_to_hex(&poseidon.hash()))
+                    .map_err(|e| Error::Io(e.to_string()))?;
+                group.clear();
+            }
+        }
+    }
+
+    if group.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Format(format!(
+            "{} trailing elements do not form a group of {}",
+            group.len(),
+            arity
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blstrs::Scalar as Fr;
+    use generic_array::typenum::U2;
+
+    #[test]
+    fn hex_roundtrip() {
+        let x = Fr::from(0x1234_5678);
+        assert_eq!(x, scalar_from_hex::<Fr>("12345678").unwrap());
+        assert_eq!(x, scalar_from_hex::<Fr>("0x012345678").unwrap());
+        assert_eq!(x, scalar_from_hex::<Fr>(&scalar_to_hex(&x)).unwrap());
+        assert_eq!(66, scalar_to_hex(&x).len());
+
+        assert!(scalar_from_hex::<Fr>("").is_err());
+        assert!(scalar_from_hex::<Fr>("0x12g4").is_err());
+        assert!(scalar_from_hex::<Fr>(&"f".repeat(64)).is_err());
+        assert!(scalar_from_hex::<Fr>(&"1".repeat(65)).is_err());
+    }
+
+    #[test]
+    fn hashes_groups_across_lines() {
+        let constants = PoseidonConstants::<Fr, U2>::new();
+        let input = "0x1 2\n3\n  0x4\n\n5 6\n";
+
+        let mut output = Vec::new();
+        hash_hex_lines(&constants, input.as_bytes(), &mut output).unwrap();
+
+        let expected: String = [(1, 2), (3, 4), (5, 6)]
+            .iter()
+            .map(|&(a, b)| {
+                let digest =
+                    Poseidon::new_with_preimage(&[Fr::from(a), Fr::from(b)], &constants).hash();
+                format!("{}\n", scalar_to_hex(&digest))
+            })
+            .collect();
+        assert_eq!(expected, String::from_utf8(output).unwrap());
+    }
+
+    #[test]
+    fn reports_malformed_input() {
+        let constants = PoseidonConstants::<Fr, U2>::new();
+
+        let err = hash_hex_lines(&constants, "1 2\n3 zz\n".as_bytes(), Vec::new()).unwrap_err();
+        assert!(matches!(err, Error::Format(ref message) if message.starts_with("line 2:")));
+
+        assert!(matches!(
+            hash_hex_lines(&constants, "1 2 3".as_bytes(), Vec::new()),
+            Err(Error::Format(_))
+        ));
+    }
+}
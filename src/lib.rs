@@ -7,10 +7,11 @@ use crate::round_numbers::{round_numbers_base, round_numbers_strengthened};
 #[cfg(test)]
 use blstrs::Scalar as Fr;
 pub use error::Error;
-use ff::PrimeField;
+use ff::{Field, PrimeField};
 use generic_array::GenericArray;
 use serde::{Deserialize, Serialize};
 use std::fmt;
+use subtle::{Choice, ConstantTimeEq};
 use trait_set::trait_set;
 
 #[cfg(all(
@@ -39,6 +40,8 @@ compile_error!("The `strengthened` feature needs the `cuda` and/or `opencl` feat
 ))]
 compile_error!("The `cuda` and `opencl` features need the `bls` and/or `pasta` feature to be set");
 
+/// Interop with circomlib's Poseidon parameters.
+pub mod circomlib;
 /// Poseidon circuit
 pub mod circuit;
 pub mod circuit2;
@@ -59,6 +62,43 @@ pub mod sponge;
 /// Hash types and domain separation tags.
 pub mod hash_type;
 
+/// Memoized constants
+#[cfg(feature = "cache")]
+pub mod cache;
+
+/// Versioned binary encoding of constants
+pub mod versioned;
+
+/// Deterministic randomness
+pub mod rng;
+
+/// Constants for several arities
+pub mod multi_arity;
+
+/// Conversions between bytes and field elements
+pub mod bytes;
+
+/// Binary Merkle trees
+pub mod merkle;
+
+/// Sorted-set accumulators with membership and non-membership proofs
+pub mod accumulator;
+
+/// Fiat-Shamir challenge generation
+pub mod challenge;
+
+/// Hash chains for running commitments
+pub mod chain;
+
+/// Nullifiers and commitments
+pub mod commitment;
+
+/// Hashing of structured values
+pub mod encoding;
+
+/// Hashing of hex-encoded field elements
+pub mod hex;
+
 /// Tree Builder
 #[cfg(any(feature = "cuda", feature = "opencl"))]
 pub mod tree_builder;
@@ -91,16 +131,33 @@ trait_set! {
    pub trait NeptuneField = PrimeField + ec_gpu::GpuName;
 }
 
+trait_set! {
+   /// The bounds on the field of constants which are both serialized and deserialized, bundled so that generic code
+   /// can write `F: PoseidonField` instead. The arity must still be bounded separately by `A: Arity<F>`, since Rust
+   /// does not imply bounds on other type parameters from a trait's bounds.
+   pub trait PoseidonField = PrimeField + Serialize + serde::de::DeserializeOwned;
+}
+
 mod serde_impl;
 
+#[cfg(all(test, feature = "compat-test"))]
+mod compat_tests;
+
 pub(crate) const TEST_SEED: [u8; 16] = [
     0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06, 0xbc, 0xe5,
 ];
 
-#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Strength {
     Standard,
     Strengthened,
+    /// **INSECURE.** Uses half the standard number of partial rounds, which is below the number required for
+    /// 128-bit security. Only for non-adversarial uses such as hash map keys or checksums, never for commitments,
+    /// Merkle trees or anything else whose security relies on Poseidon. Its digests are domain-separated from those
+    /// of the secure strengths (see [`hash_type::HashType::domain_tag_with_strength`]), so they never collide with
+    /// secure digests of the same preimage. It is not supported on the GPU or in circuits.
+    #[cfg(feature = "insecure-fast")]
+    Fast,
 }
 
 impl fmt::Display for Strength {
@@ -108,6 +165,8 @@ impl fmt::Display for Strength {
         match self {
             Self::Standard => write!(f, "standard"),
             Self::Strengthened => write!(f, "strengthened"),
+            #[cfg(feature = "insecure-fast")]
+            Self::Fast => write!(f, "fast"),
         }
     }
 }
@@ -148,6 +207,11 @@ pub fn round_numbers(arity: usize, strength: &Strength) -> (usize, usize) {
     match strength {
         Strength::Standard => round_numbers_base(arity),
         Strength::Strengthened => round_numbers_strengthened(arity),
+        #[cfg(feature = "insecure-fast")]
+        Strength::Fast => {
+            let (full_rounds, partial_rounds) = round_numbers_base(arity);
+            (full_rounds, partial_rounds / 2)
+        }
     }
 }
 
@@ -166,6 +230,12 @@ pub(crate) fn scalar_from_u64s(parts: [u64; 4]) -> Fr {
 const SBOX: u8 = 1; // x^5
 const FIELD: u8 = 1; // Gf(p)
 
+/// Returns whether `F::Repr` is little-endian, as for the BLS12-381 and Pasta scalar fields. Otherwise it is
+/// big-endian, as for the secp256k1 scalar field.
+pub(crate) fn repr_is_little_endian<F: PrimeField>() -> bool {
+    F::ONE.to_repr().as_ref()[0] == 1
+}
+
 fn round_constants<F: PrimeField>(arity: usize, strength: &Strength) -> Vec<F> {
     let t = arity + 1;
 
@@ -198,9 +268,101 @@ pub(crate) fn quintic_s_box<F: PrimeField>(l: &mut F, pre_add: Option<&F>, post_
     }
 }
 
+/// Invert every element of `v` in place using Montgomery's trick, which needs a single field inversion for the whole
+/// slice. Zero elements have no inverse and are left unchanged.
+pub fn batch_invert<F: Field>(v: &mut [F]) {
+    // prefix[i] holds the product of all non-zero elements before index i.
+    let mut prefix = Vec::with_capacity(v.len());
+    let mut acc = F::ONE;
+    for x in v.iter() {
+        prefix.push(acc);
+        if !bool::from(x.is_zero()) {
+            acc *= x;
+        }
+    }
+
+    // acc is a product of non-zero elements, so it is invertible.
+    let mut inv = acc.invert().unwrap();
+    for (x, prefix) in v.iter_mut().zip(prefix).rev() {
+        if !bool::from(x.is_zero()) {
+            let x_inv = inv * prefix;
+            inv *= *x;
+            *x = x_inv;
+        }
+    }
+}
+
+/// Compares two digests in constant time: the running time does not depend on their values. `==` on field elements
+/// makes no such guarantee, so digests which authenticate data (such as MACs or commitments compared against a
+/// secret value) should be compared with this function. The guarantee is that of the field's `ConstantTimeEq`
+/// implementation, which every `ff::Field` provides.
+pub fn ct_eq_digest<F: PrimeField>(a: &F, b: &F) -> Choice {
+    a.ct_eq(b)
+}
+
+/// Deterministically generates `len` field elements from `seed`, for reproducible test and benchmark preimages. Each
+/// element is `F::from` of the SplitMix64 output for a counter starting at `seed`, so elements are spread over 64
+/// bits and the same seed always yields the same preimage. Not suitable where unpredictable elements are needed.
+pub fn fill_preimage<F: PrimeField>(seed: u64, len: usize) -> Vec<F> {
+    (0..len as u64)
+        .map(|i| {
+            let mut z = seed.wrapping_add(i.wrapping_add(1).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            F::from(z ^ (z >> 31))
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::poseidon::PoseidonConstants;
+    use generic_array::typenum::U2;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    #[test]
+    fn test_ct_eq_digest() {
+        let constants = PoseidonConstants::<Fr, U2>::new();
+        let a = Poseidon::new_with_preimage(&[Fr::ONE, Fr::ZERO], &constants).hash();
+        let b = Poseidon::new_with_preimage(&[Fr::ZERO, Fr::ONE], &constants).hash();
+
+        assert!(bool::from(ct_eq_digest(&a, &a)));
+        assert!(!bool::from(ct_eq_digest(&a, &b)));
+        assert!(!bool::from(ct_eq_digest(&a, &(a + Fr::ONE))));
+    }
+
+    #[test]
+    fn test_fill_preimage() {
+        let preimage = fill_preimage::<Fr>(42, 100);
+        assert_eq!(100, preimage.len());
+        assert_eq!(preimage, fill_preimage::<Fr>(42, 100));
+        assert_eq!(preimage[..10], fill_preimage::<Fr>(42, 10)[..]);
+        assert_ne!(preimage, fill_preimage::<Fr>(43, 100));
+        for (i, x) in preimage.iter().enumerate() {
+            assert!(preimage[i + 1..].iter().all(|y| x != y));
+        }
+    }
+
+    #[test]
+    fn test_batch_invert() {
+        let mut rng = XorShiftRng::from_seed(TEST_SEED);
+        let mut v: Vec<Fr> = (0..64).map(|_| Fr::random(&mut rng)).collect();
+        v[0] = Fr::ZERO;
+        v[17] = Fr::ZERO;
+        v[63] = Fr::ZERO;
+
+        let expected: Vec<Fr> = v
+            .iter()
+            .map(|x| Option::from(x.invert()).unwrap_or(Fr::ZERO))
+            .collect();
+        batch_invert(&mut v);
+        assert_eq!(expected, v);
+
+        let mut empty: Vec<Fr> = Vec::new();
+        batch_invert(&mut empty);
+    }
 
     #[test]
     fn test_strengthened_round_numbers() {
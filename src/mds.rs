@@ -8,6 +8,7 @@ use crate::matrix;
 use crate::matrix::{
     apply_matrix, invert, is_identity, is_invertible, is_square, mat_mul, minor, transpose, Matrix,
 };
+use crate::Error;
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct MdsMatrices<F: PrimeField> {
@@ -19,26 +20,85 @@ pub struct MdsMatrices<F: PrimeField> {
     pub m_double_prime: Matrix<F>,
 }
 
+impl<F: PrimeField> MdsMatrices<F> {
+    /// Keeps only `m`, leaving the other matrices empty. Hashing in the default mode only needs `m`; the others are
+    /// only used to derive the optimized constants.
+    pub(crate) fn only_m(m: Matrix<F>) -> Self {
+        Self {
+            m,
+            m_inv: Vec::new(),
+            m_hat: Vec::new(),
+            m_hat_inv: Vec::new(),
+            m_prime: Vec::new(),
+            m_double_prime: Vec::new(),
+        }
+    }
+}
+
+/// The source of the MDS matrix of generated constants.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum MdsSource<F: PrimeField> {
+    /// The Cauchy matrix `1 / (x_i + y_j)` with `x_i = i` and `y_j = width + j`, as generated by default.
+    #[default]
+    Cauchy,
+    /// A fixed matrix, such as one embedded in another implementation. It is applied as `state * matrix`, like the
+    /// matrix taken by [`crate::poseidon::PoseidonConstants::from_parts`]. Only its invertibility and that of its minor
+    /// are checked; that it is MDS is the caller's responsibility.
+    Fixed(Matrix<F>),
+}
+
+impl<F: PrimeField> MdsSource<F> {
+    /// Returns the MDS matrices for state width `t`, or [`Error::MdsGenerationFailed`] if a fixed matrix is not `t`
+    /// by `t`, or if no Cauchy matrix of that width exists for the field.
+    pub fn try_mds_matrices(&self, t: usize) -> Result<MdsMatrices<F>, Error> {
+        match self {
+            MdsSource::Cauchy => try_create_mds_matrices(t),
+            MdsSource::Fixed(m) => {
+                if m.len() != t || m.iter().any(|row| row.len() != t) {
+                    return Err(Error::MdsGenerationFailed(format!(
+                        "fixed MDS matrix must be square of size {t}"
+                    )));
+                }
+                try_derive_mds_matrices(m.clone())
+            }
+        }
+    }
+}
+
 pub fn create_mds_matrices<F: PrimeField>(t: usize) -> MdsMatrices<F> {
-    let m = generate_mds(t);
-    derive_mds_matrices(m)
+    try_create_mds_matrices(t).expect("MDS generation failed")
+}
+
+/// Like [`create_mds_matrices`], but returns [`Error::MdsGenerationFailed`] instead of panicking, as happens for
+/// fields too small for the width.
+pub fn try_create_mds_matrices<F: PrimeField>(t: usize) -> Result<MdsMatrices<F>, Error> {
+    let m = try_generate_mds(t)?;
+    try_derive_mds_matrices(m)
 }
 
 pub fn derive_mds_matrices<F: PrimeField>(m: Matrix<F>) -> MdsMatrices<F> {
-    let m_inv = invert(&m).unwrap(); // m is MDS so invertible.
+    // m is MDS so invertible, and so is its minor. If not, `mds_matrix` was not correctly generated.
+    try_derive_mds_matrices(m).expect("MDS matrix or its minor is not invertible")
+}
+
+/// Like [`derive_mds_matrices`], but returns [`Error::MdsGenerationFailed`] if `m` or its minor is not invertible.
+pub fn try_derive_mds_matrices<F: PrimeField>(m: Matrix<F>) -> Result<MdsMatrices<F>, Error> {
+    let not_invertible =
+        |what: &str| Error::MdsGenerationFailed(format!("{what} is not invertible"));
+    let m_inv = invert(&m).ok_or_else(|| not_invertible("MDS matrix"))?;
     let m_hat = minor(&m, 0, 0);
-    let m_hat_inv = invert(&m_hat).unwrap(); // If this returns None, then `mds_matrix` was not correctly generated.
+    let m_hat_inv = invert(&m_hat).ok_or_else(|| not_invertible("minor of the MDS matrix"))?;
     let m_prime = make_prime(&m);
     let m_double_prime = make_double_prime(&m, &m_hat_inv);
 
-    MdsMatrices {
+    Ok(MdsMatrices {
         m,
         m_inv,
         m_hat,
         m_hat_inv,
         m_prime,
         m_double_prime,
-    }
+    })
 }
 
 /// A `SparseMatrix` is specifically one of the form of M''.
@@ -64,6 +124,41 @@ impl<F: PrimeField> SparseMatrix<F> {
         Self { w_hat, v_rest }
     }
 
+    /// Multiplies `state`, as a row vector, by the matrix in place (`state * M''`), as the partial rounds of the
+    /// optimized permutation do. This takes `2 * size - 1` multiplications instead of the `size^2` of a dense matrix.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of `state` is not the size of the matrix.
+    pub fn apply(&self, state: &mut [F]) {
+        assert_eq!(
+            self.size(),
+            state.len(),
+            "Sparse matrix can only be applied to a state of the same size."
+        );
+        self.apply_unchecked(state);
+    }
+
+    /// Like [`SparseMatrix::apply`], with the size checked only in debug builds. The permutation uses this, since its
+    /// state always has the width of the constants the matrix belongs to.
+    pub(crate) fn apply_unchecked(&self, state: &mut [F]) {
+        debug_assert_eq!(self.size(), state.len());
+
+        // First column is dense.
+        let first = self
+            .w_hat
+            .iter()
+            .zip(state.iter())
+            .fold(F::ZERO, |acc, (w, s)| acc + *w * s);
+
+        // Except for first row/column, diagonals are one. First row is dense.
+        let old_first = state[0];
+        for (s, v) in state[1..].iter_mut().zip(&self.v_rest) {
+            *s += *v * old_first;
+        }
+        state[0] = first;
+    }
+
     pub fn is_sparse_matrix(m: &Matrix<F>) -> bool {
         is_square(m) && is_identity(&minor(m, 0, 0))
     }
@@ -94,31 +189,47 @@ pub fn factor_to_sparse_matrixes<F: PrimeField>(
     base_matrix: Matrix<F>,
     n: usize,
 ) -> (Matrix<F>, Vec<SparseMatrix<F>>) {
-    let (pre_sparse, sparse_matrices) = factor_to_sparse_matrices(base_matrix, n);
+    try_factor_to_sparse_matrixes(base_matrix, n).expect("sparse factorization failed")
+}
+
+/// Like [`factor_to_sparse_matrixes`], but returns [`Error::MdsGenerationFailed`] if some intermediate matrix of the
+/// factorization has a minor which is not invertible.
+pub fn try_factor_to_sparse_matrixes<F: PrimeField>(
+    base_matrix: Matrix<F>,
+    n: usize,
+) -> Result<(Matrix<F>, Vec<SparseMatrix<F>>), Error> {
+    let (pre_sparse, sparse_matrices) = try_factor_to_sparse_matrices(base_matrix, n)?;
     let sparse_matrixes = sparse_matrices
         .iter()
         .map(|m| SparseMatrix::<F>::new(m.to_vec()))
         .collect::<Vec<_>>();
 
-    (pre_sparse, sparse_matrixes)
+    Ok((pre_sparse, sparse_matrixes))
 }
 
 pub fn factor_to_sparse_matrices<F: PrimeField>(
     base_matrix: Matrix<F>,
     n: usize,
 ) -> (Matrix<F>, Vec<Matrix<F>>) {
+    try_factor_to_sparse_matrices(base_matrix, n).expect("sparse factorization failed")
+}
+
+fn try_factor_to_sparse_matrices<F: PrimeField>(
+    base_matrix: Matrix<F>,
+    n: usize,
+) -> Result<(Matrix<F>, Vec<Matrix<F>>), Error> {
     let (pre_sparse, mut all) =
-        (0..n).fold((base_matrix.clone(), Vec::new()), |(curr, mut acc), _| {
-            let derived = derive_mds_matrices(curr);
+        (0..n).try_fold((base_matrix.clone(), Vec::new()), |(curr, mut acc), _| {
+            let derived = try_derive_mds_matrices(curr)?;
             acc.push(derived.m_double_prime);
             let new = mat_mul(&base_matrix, &derived.m_prime).unwrap();
-            (new, acc)
-        });
+            Ok::<_, Error>((new, acc))
+        })?;
     all.reverse();
-    (pre_sparse, all)
+    Ok((pre_sparse, all))
 }
 
-fn generate_mds<F: PrimeField>(t: usize) -> Matrix<F> {
+fn try_generate_mds<F: PrimeField>(t: usize) -> Result<Matrix<F>, Error> {
     // Source: https://github.com/dusk-network/dusk-poseidon-merkle/commit/776c37734ea2e71bb608ce4bc58fdb5f208112a7#diff-2eee9b20fb23edcc0bf84b14167cbfdc
     // Generate x and y values deterministically for the cauchy matrix
     // where x[i] != y[i] to allow the values to be inverted
@@ -136,20 +247,29 @@ fn generate_mds<F: PrimeField>(t: usize) -> Matrix<F> {
             ys.iter()
                 .map(|ys_item| {
                     // Generate the entry at (i,j)
+                    // In fields smaller than the width, x + y may be zero.
                     let mut tmp = *xs_item;
                     tmp.add_assign(ys_item);
-                    tmp.invert().unwrap()
+                    Option::from(tmp.invert()).ok_or_else(|| {
+                        Error::MdsGenerationFailed(format!(
+                            "Cauchy matrix of width {t} has a zero denominator in this field"
+                        ))
+                    })
                 })
-                .collect()
+                .collect::<Result<Vec<F>, Error>>()
         })
-        .collect();
+        .collect::<Result<Matrix<F>, Error>>()?;
 
     // To ensure correctness, we would check all sub-matrices for invertibility. Meanwhile, this is a simple sanity check.
-    assert!(is_invertible(&matrix));
+    if !is_invertible(&matrix) {
+        return Err(Error::MdsGenerationFailed(format!(
+            "Cauchy matrix of width {t} is not invertible in this field"
+        )));
+    }
 
     //  `poseidon::product_mds_with_matrix` relies on the constructed MDS matrix being symmetric, so ensure it is.
     assert_eq!(matrix, transpose(&matrix));
-    matrix
+    Ok(matrix)
 }
 
 fn make_prime<F: PrimeField>(m: &Matrix<F>) -> Matrix<F> {
@@ -297,7 +417,7 @@ mod tests {
     fn test_factor_to_sparse_matrices_aux(width: usize, n: usize) {
         let mut rng = XorShiftRng::from_seed(crate::TEST_SEED);
 
-        let m = generate_mds::<Fr>(width);
+        let m = try_generate_mds::<Fr>(width).unwrap();
         let m2 = m.clone();
 
         let (pre_sparse, mut sparse) = factor_to_sparse_matrices(m, n);
@@ -334,6 +454,35 @@ mod tests {
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn sparse_apply_matches_dense() {
+        let mut rng = XorShiftRng::from_seed(crate::TEST_SEED);
+        let n = 4;
+        for width in 2..9 {
+            let m = try_generate_mds::<Fr>(width).unwrap();
+            let (pre_sparse, sparse) = factor_to_sparse_matrixes(m.clone(), n);
+            let initial: Vec<Fr> = (0..width).map(|_| Fr::random(&mut rng)).collect();
+
+            // Without S-boxes in between, the factors compose to `n + 1` applications of the MDS matrix.
+            let expected = (0..=n).fold(initial.clone(), |acc, _| apply_matrix(&m, &acc));
+
+            let mut actual = apply_matrix(&pre_sparse, &initial);
+            for sparse_matrix in &sparse {
+                let dense = apply_matrix(&sparse_matrix.to_matrix(), &actual);
+                sparse_matrix.apply(&mut actual);
+                assert_eq!(dense, actual);
+            }
+            assert_eq!(expected, actual);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "same size")]
+    fn sparse_apply_checks_size() {
+        let (_, sparse) = factor_to_sparse_matrixes(try_generate_mds::<Fr>(3).unwrap(), 1);
+        sparse[0].apply(&mut [Fr::ONE; 4]);
+    }
+
     #[test]
     fn test_factor_to_sparse_matrixes() {
         for width in 3..9 {
@@ -342,7 +491,7 @@ mod tests {
     }
 
     fn test_factor_to_sparse_matrixes_aux(width: usize, n: usize) {
-        let m = generate_mds::<Fr>(width);
+        let m = try_generate_mds::<Fr>(width).unwrap();
         let m2 = m.clone();
 
         let (pre_sparse, sparse_matrices) = factor_to_sparse_matrices(m, n);
@@ -0,0 +1,899 @@
+//! Binary Merkle trees over Poseidon.
+//!
+//! Interior nodes are the arity-2 Poseidon digest of their children (see [`compress`]). When a level has an odd
+//! number of nodes, the last node is by default compressed with zero, so trees need not have a power-of-two number of
+//! leaves. Alternatively, odd levels can be padded with another element, or their last node promoted unchanged to the
+//! next level (see [`Padding`]).
+use crate::poseidon::{hash_exact, Poseidon, PoseidonConstants};
+use crate::{ct_eq_digest, Arity, Error};
+use ff::PrimeField;
+use generic_array::typenum::U2;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// Compresses two nodes into their parent. The digest is that of [`Poseidon::hash`] with preimage `[left, right]`,
+/// computed over a three-element array instead of a [`Poseidon`] state.
+pub fn compress<F: PrimeField>(constants: &PoseidonConstants<F, U2>, left: F, right: F) -> F {
+    compress_width3(constants, left, right)
+}
+
+/// The optimized static permutation (see [`Poseidon::hash_optimized_static`]) for width 3. The rounds are iterated as
+/// in the generic permutation; only the products by the dense and sparse matrices are written out for three elements.
+fn compress_width3<F: PrimeField>(constants: &PoseidonConstants<F, U2>, left: F, right: F) -> F {
+    fn s_box<F: PrimeField>(x: F) -> F {
+        x.square().square() * x
+    }
+    // Row vector times matrix, as in `Poseidon::product_mds_with_matrix`.
+    fn product<F: PrimeField>(s: [F; 3], m: &[Vec<F>]) -> [F; 3] {
+        [
+            s[0] * m[0][0] + s[1] * m[1][0] + s[2] * m[2][0],
+            s[0] * m[0][1] + s[1] * m[1][1] + s[2] * m[2][1],
+            s[0] * m[0][2] + s[1] * m[1][2] + s[2] * m[2][2],
+        ]
+    }
+
+    let rc = &constants.compressed_round_constants;
+    let m = &constants.mds_matrices.m;
+    let half_full_rounds = constants.half_full_rounds;
+
+    let mut s = [constants.domain_tag + rc[0], left + rc[1], right + rc[2]];
+    let mut offset = 3;
+
+    for round in 0..half_full_rounds {
+        s = [
+            s_box(s[0]) + rc[offset],
+            s_box(s[1]) + rc[offset + 1],
+            s_box(s[2]) + rc[offset + 2],
+        ];
+        offset += 3;
+        let matrix = if round == half_full_rounds - 1 {
+            &constants.pre_sparse_matrix
+        } else {
+            m
+        };
+        s = product(s, matrix);
+    }
+
+    for sparse in &constants.sparse_matrixes[..constants.partial_rounds] {
+        let first = s_box(s[0]) + rc[offset];
+        offset += 1;
+        let (w, v) = (&sparse.w_hat, &sparse.v_rest);
+        s = [
+            w[0] * first + w[1] * s[1] + w[2] * s[2],
+            s[1] + v[0] * first,
+            s[2] + v[1] * first,
+        ];
+    }
+
+    for _ in 1..half_full_rounds {
+        s = [
+            s_box(s[0]) + rc[offset],
+            s_box(s[1]) + rc[offset + 1],
+            s_box(s[2]) + rc[offset + 2],
+        ];
+        offset += 3;
+        s = product(s, m);
+    }
+    s = product([s_box(s[0]), s_box(s[1]), s_box(s[2])], m);
+
+    debug_assert_eq!(offset, rc.len());
+    s[1]
+}
+
+/// How a level with an odd number of nodes is completed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Padding<F> {
+    /// The last node is promoted unchanged to the next level.
+    Promote,
+    /// The last node is compressed with the given padding element, as in implementations which pad each level to a
+    /// full arity.
+    Element(F),
+}
+
+impl<F: PrimeField> Padding<F> {
+    /// Pads with zero, the most common padding element.
+    pub fn zero() -> Self {
+        Padding::Element(F::ZERO)
+    }
+}
+
+/// Hashes each pair of nodes of `level` with `p`, completing an odd last node according to `padding`.
+fn next_level<F: PrimeField>(
+    constants: &PoseidonConstants<F, U2>,
+    level: &[F],
+    padding: Padding<F>,
+) -> Vec<F> {
+    level
+        .chunks(2)
+        .map(|pair| hash_pair(constants, pair, padding))
+        .collect()
+}
+
+fn hash_pair<F: PrimeField>(
+    constants: &PoseidonConstants<F, U2>,
+    pair: &[F],
+    padding: Padding<F>,
+) -> F {
+    match (pair, padding) {
+        ([left, right], _) => compress(constants, *left, *right),
+        ([node], Padding::Promote) => *node,
+        ([node], Padding::Element(pad)) => compress(constants, *node, pad),
+        _ => unreachable!("chunks of two"),
+    }
+}
+
+/// Computes the root of the binary Merkle tree with the given leaves, padding odd levels with zero. Returns an error
+/// if there are no leaves.
+pub fn merkle_root<F: PrimeField>(
+    constants: &PoseidonConstants<F, U2>,
+    leaves: &[F],
+) -> Result<F, Error> {
+    merkle_root_with_padding(constants, leaves, Padding::zero())
+}
+
+/// Computes the root of the binary Merkle tree with the given leaves, completing odd levels according to `padding`.
+/// The choice of padding affects the root whenever some level has an odd number of nodes. Returns an error if there
+/// are no leaves.
+pub fn merkle_root_with_padding<F: PrimeField>(
+    constants: &PoseidonConstants<F, U2>,
+    leaves: &[F],
+    padding: Padding<F>,
+) -> Result<F, Error> {
+    if leaves.is_empty() {
+        return Err(Error::Other(
+            "cannot compute the root of an empty tree".to_string(),
+        ));
+    }
+
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        level = next_level(constants, &level, padding);
+    }
+    Ok(level[0])
+}
+
+/// Parallel version of [`merkle_root`]. The nodes of each level are hashed across rayon threads, and the result is the
+/// same as that of [`merkle_root`].
+#[cfg(feature = "parallel")]
+pub fn par_merkle_root<F: PrimeField>(
+    constants: &PoseidonConstants<F, U2>,
+    leaves: &[F],
+) -> Result<F, Error> {
+    par_merkle_root_with_padding(constants, leaves, Padding::zero())
+}
+
+/// Parallel version of [`merkle_root_with_padding`].
+#[cfg(feature = "parallel")]
+pub fn par_merkle_root_with_padding<F: PrimeField>(
+    constants: &PoseidonConstants<F, U2>,
+    leaves: &[F],
+    padding: Padding<F>,
+) -> Result<F, Error> {
+    if leaves.is_empty() {
+        return Err(Error::Other(
+            "cannot compute the root of an empty tree".to_string(),
+        ));
+    }
+
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        level = level
+            .par_chunks(2)
+            .map(|pair| hash_pair(constants, pair, padding))
+            .collect();
+    }
+    Ok(level[0])
+}
+
+/// A binary Merkle tree which retains its layers, for producing proofs at any level.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MerkleTree<F: PrimeField> {
+    /// All layers from the leaves up to the root, or none if layers were discarded.
+    layers: Vec<Vec<F>>,
+    num_layers: usize,
+    root: F,
+    padding: Padding<F>,
+}
+
+impl<F: PrimeField> MerkleTree<F> {
+    /// Builds the tree with the given leaves, padding odd levels with zero as [`merkle_root`] does, and retains all
+    /// layers. Returns an error if there are no leaves.
+    pub fn build(constants: &PoseidonConstants<F, U2>, leaves: &[F]) -> Result<Self, Error> {
+        Self::build_with_options(constants, leaves, Padding::zero(), false)
+    }
+
+    /// Builds the tree with the given leaves, completing odd levels according to `padding`. If `discard_layers` is
+    /// set, only the root is kept, so memory use is that of [`merkle_root_with_padding`]. Returns an error if there
+    /// are no leaves.
+    pub fn build_with_options(
+        constants: &PoseidonConstants<F, U2>,
+        leaves: &[F],
+        padding: Padding<F>,
+        discard_layers: bool,
+    ) -> Result<Self, Error> {
+        if leaves.is_empty() {
+            return Err(Error::Other(
+                "cannot compute the root of an empty tree".to_string(),
+            ));
+        }
+
+        let mut layers = vec![leaves.to_vec()];
+        let mut num_layers = 1;
+        while layers.last().unwrap().len() > 1 {
+            let next = next_level(constants, layers.last().unwrap(), padding);
+            if discard_layers {
+                layers.clear();
+            }
+            layers.push(next);
+            num_layers += 1;
+        }
+
+        let root = layers.last().unwrap()[0];
+        if discard_layers {
+            layers.clear();
+        }
+        Ok(Self {
+            layers,
+            num_layers,
+            root,
+            padding,
+        })
+    }
+
+    pub fn root(&self) -> F {
+        self.root
+    }
+
+    /// Returns the number of layers, leaves and root included. This does not depend on whether layers were
+    /// discarded.
+    pub fn num_layers(&self) -> usize {
+        self.num_layers
+    }
+
+    /// Returns the nodes of layer `depth`, counting from the leaves at depth 0 up to the root at depth
+    /// `num_layers() - 1`. Returns `None` if `depth` is out of range or the layers were discarded.
+    pub fn layer(&self, depth: usize) -> Option<&[F]> {
+        self.layers.get(depth).map(Vec::as_slice)
+    }
+
+    /// Returns the inclusion proof of the leaf at `index`, or `None` if `index` is out of range or the layers were
+    /// discarded.
+    pub fn proof(&self, index: usize) -> Option<MerkleProof<F>> {
+        let leaf = *self.layers.first()?.get(index)?;
+        let siblings = (0..self.num_layers - 1)
+            .map(|depth| self.sibling(depth, index >> depth))
+            .collect();
+
+        Some(MerkleProof {
+            index,
+            leaf,
+            siblings,
+        })
+    }
+
+    /// Returns the inclusion proofs of all leaves, in order, or no proofs if the layers were discarded. The proofs are
+    /// read from the retained layers in one pass over them, without hashing, so this takes `O(n log n)` time for `n`
+    /// leaves rather than rebuilding the tree per proof.
+    pub fn all_proofs(&self) -> Vec<MerkleProof<F>> {
+        let leaves = match self.layers.first() {
+            Some(leaves) => leaves,
+            None => return Vec::new(),
+        };
+
+        let mut proofs: Vec<MerkleProof<F>> = leaves
+            .iter()
+            .enumerate()
+            .map(|(index, leaf)| MerkleProof {
+                index,
+                leaf: *leaf,
+                siblings: Vec::with_capacity(self.num_layers - 1),
+            })
+            .collect();
+        for depth in 0..self.num_layers - 1 {
+            for proof in proofs.iter_mut() {
+                proof
+                    .siblings
+                    .push(self.sibling(depth, proof.index >> depth));
+            }
+        }
+        proofs
+    }
+
+    /// Replaces the leaf at `index` and rehashes the nodes on its path, reusing all other nodes, so an update takes
+    /// `O(log n)` hashes. Returns the new root, or [`Error::IndexOutOfBounds`] if `index` is out of range. Requires the
+    /// layers, and returns an error if they were discarded.
+    pub fn update_leaf(
+        &mut self,
+        constants: &PoseidonConstants<F, U2>,
+        index: usize,
+        new_leaf: F,
+    ) -> Result<F, Error> {
+        let leaves = self
+            .layers
+            .first_mut()
+            .ok_or_else(|| Error::Other("cannot update a tree without layers".to_string()))?;
+        *leaves.get_mut(index).ok_or(Error::IndexOutOfBounds)? = new_leaf;
+
+        for depth in 0..self.num_layers - 1 {
+            let position = index >> depth;
+            let layer = &self.layers[depth];
+            let start = position & !1;
+            let pair = &layer[start..layer.len().min(start + 2)];
+            let parent = hash_pair(constants, pair, self.padding);
+            self.layers[depth + 1][position >> 1] = parent;
+        }
+
+        self.root = self.layers[self.num_layers - 1][0];
+        Ok(self.root)
+    }
+
+    /// Returns the sibling of the node at `position` in layer `depth`, as completed by the padding if it has none.
+    fn sibling(&self, depth: usize, position: usize) -> Option<F> {
+        match (self.layers[depth].get(position ^ 1), self.padding) {
+            (Some(sibling), _) => Some(*sibling),
+            (None, Padding::Promote) => None,
+            (None, Padding::Element(pad)) => Some(pad),
+        }
+    }
+}
+
+/// An inclusion proof of a leaf in a [`MerkleTree`]. The proof does not commit to the number of leaves, which the
+/// verifier must know to check that the leaf index is in range.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MerkleProof<F: PrimeField> {
+    pub index: usize,
+    pub leaf: F,
+    /// The sibling of the node on the path from the leaf, for each layer from the leaves up, or `None` if the node
+    /// was promoted without a sibling.
+    pub siblings: Vec<Option<F>>,
+}
+
+impl<F: PrimeField> MerkleProof<F> {
+    /// Returns the root implied by the proof.
+    pub fn root(&self, constants: &PoseidonConstants<F, U2>) -> F {
+        recompute_root(constants, self.leaf, &self.path())
+    }
+
+    /// Returns the path of the proof in the form taken by [`recompute_root`].
+    pub fn path(&self) -> Vec<(F, bool)> {
+        self.siblings
+            .iter()
+            .enumerate()
+            .filter_map(|(depth, sibling)| {
+                sibling.map(|sibling| (sibling, (self.index >> depth) % 2 == 1))
+            })
+            .collect()
+    }
+
+    /// Checks that the proof leads to `root`. The roots are compared in constant time (see [`ct_eq_digest`]).
+    pub fn verify(&self, constants: &PoseidonConstants<F, U2>, root: F) -> bool {
+        bool::from(ct_eq_digest(&self.root(constants), &root))
+    }
+}
+
+/// Recomputes a root from a leaf and its path, without a [`MerkleProof`]. Each entry of `path` is a sibling, from the
+/// leaves up, and whether that sibling is the left child, and so is compressed as the left input. Promoted nodes,
+/// which have no sibling, have no entry.
+pub fn recompute_root<F: PrimeField>(
+    constants: &PoseidonConstants<F, U2>,
+    leaf: F,
+    path: &[(F, bool)],
+) -> F {
+    path.iter().fold(leaf, |node, &(sibling, sibling_is_left)| {
+        if sibling_is_left {
+            compress(constants, sibling, node)
+        } else {
+            compress(constants, node, sibling)
+        }
+    })
+}
+
+/// Returns the digests of all-empty subtrees of heights `0..=depth`: level 0 is `empty_leaf`, and each following level
+/// compresses the previous one with itself. The result depends only on its arguments, so it can be computed once and
+/// reused across sparse or padded trees.
+pub fn empty_subtree_digests<F: PrimeField>(
+    constants: &PoseidonConstants<F, U2>,
+    depth: usize,
+    empty_leaf: F,
+) -> Vec<F> {
+    let mut digests = Vec::with_capacity(depth + 1);
+    digests.push(empty_leaf);
+    for _ in 0..depth {
+        let below = *digests.last().unwrap();
+        digests.push(compress(constants, below, below));
+    }
+    digests
+}
+
+/// Builds a binary Merkle tree incrementally as leaves arrive, keeping only the roots of the complete subtrees seen
+/// so far (as in a Merkle mountain range). Memory is logarithmic in the number of leaves. [`finalize`] returns the
+/// root [`merkle_root_with_padding`] computes for all the leaves with the builder's padding, which is zero unless
+/// given to [`BatchMerkleBuilder::new_with_padding`].
+///
+/// [`finalize`]: BatchMerkleBuilder::finalize
+#[derive(Clone, Debug)]
+pub struct BatchMerkleBuilder<'a, F: PrimeField> {
+    constants: &'a PoseidonConstants<F, U2>,
+    padding: Padding<F>,
+    /// `peaks[h]` holds the root of a complete subtree of height `h`, if there is one. The set bits of the number of
+    /// leaves correspond to the present peaks.
+    peaks: Vec<Option<F>>,
+    leaf_count: usize,
+}
+
+impl<'a, F: PrimeField> BatchMerkleBuilder<'a, F> {
+    /// Creates a builder padding odd levels with zero, like [`merkle_root`].
+    pub fn new(constants: &'a PoseidonConstants<F, U2>) -> Self {
+        Self::new_with_padding(constants, Padding::zero())
+    }
+
+    pub fn new_with_padding(constants: &'a PoseidonConstants<F, U2>, padding: Padding<F>) -> Self {
+        Self {
+            constants,
+            padding,
+            peaks: Vec::new(),
+            leaf_count: 0,
+        }
+    }
+
+    pub fn leaf_count(&self) -> usize {
+        self.leaf_count
+    }
+
+    pub fn add_leaf(&mut self, leaf: F) {
+        // Adding a leaf increments the binary counter of leaves: merge equal-height subtrees while carrying.
+        let mut node = leaf;
+        let mut height = 0;
+        while let Some(peak) = self.peaks.get_mut(height).and_then(Option::take) {
+            node = compress(self.constants, peak, node);
+            height += 1;
+        }
+        if height == self.peaks.len() {
+            self.peaks.push(None);
+        }
+        self.peaks[height] = Some(node);
+        self.leaf_count += 1;
+    }
+
+    /// Returns the root of the tree. Returns an error if no leaves were added.
+    pub fn finalize(self) -> Result<F, Error> {
+        let top = self
+            .peaks
+            .iter()
+            .rposition(Option::is_some)
+            .ok_or_else(|| Error::Other("cannot compute the root of an empty tree".to_string()))?;
+
+        // Walk up from the leaves, carrying the last node of each level that is not part of a peak. A peak or carried
+        // node which is alone at its height is the odd last node of its level, except at the top.
+        let mut carry = None;
+        for height in 0..top {
+            carry = match (self.peaks[height], carry) {
+                (Some(peak), Some(node)) => Some(compress(self.constants, peak, node)),
+                (Some(node), None) | (None, Some(node)) => {
+                    Some(hash_pair(self.constants, &[node], self.padding))
+                }
+                (None, None) => None,
+            };
+        }
+
+        let peak = self.peaks[top].expect("the top peak is present");
+        Ok(match carry {
+            Some(node) => compress(self.constants, peak, node),
+            None => peak,
+        })
+    }
+}
+
+/// The hash functions of a binary Merkle tree, as expected by generic tree implementations: leaves are hashed from
+/// their data, and inner nodes from their two children.
+pub trait MerkleHasher {
+    type Domain;
+
+    fn hash_leaf(&self, data: &[Self::Domain]) -> Self::Domain;
+
+    fn hash_inner(&self, left: Self::Domain, right: Self::Domain) -> Self::Domain;
+}
+
+/// Implements [`MerkleHasher`] with Poseidon, so that generic trees can use it. Leaf data is hashed with
+/// [`hash_exact`] using constants of arity `A`, and inner nodes with [`compress`], so that a tree over hashed leaves
+/// has the root [`merkle_root_with_padding`] computes for the leaf digests with the tree's padding.
+#[derive(Clone, Debug)]
+pub struct PoseidonMerkleHasher<F: PrimeField, A: Arity<F>> {
+    leaf_constants: PoseidonConstants<F, A>,
+    inner_constants: PoseidonConstants<F, U2>,
+}
+
+impl<F: PrimeField, A: Arity<F>> PoseidonMerkleHasher<F, A> {
+    pub fn new() -> Self {
+        Self {
+            leaf_constants: PoseidonConstants::new(),
+            inner_constants: PoseidonConstants::new(),
+        }
+    }
+
+    /// Returns the constants used for inner nodes, for use with the functions of this module.
+    pub fn inner_constants(&self) -> &PoseidonConstants<F, U2> {
+        &self.inner_constants
+    }
+}
+
+impl<F: PrimeField, A: Arity<F>> Default for PoseidonMerkleHasher<F, A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: PrimeField, A: Arity<F>> MerkleHasher for PoseidonMerkleHasher<F, A> {
+    type Domain = F;
+
+    /// Hashes leaf data of any length; see [`hash_exact`].
+    fn hash_leaf(&self, data: &[F]) -> F {
+        hash_exact(&self.leaf_constants, data.iter().copied())
+            .expect("slices report their exact length")
+    }
+
+    fn hash_inner(&self, left: F, right: F) -> F {
+        compress(&self.inner_constants, left, right)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blstrs::Scalar as Fr;
+    use ff::Field;
+
+    /// Builds a tree with a generic hasher, as a tree library would, padding odd levels with the default element.
+    fn generic_root<H: MerkleHasher>(hasher: &H, leaves: &[Vec<H::Domain>]) -> H::Domain
+    where
+        H::Domain: Clone + Default,
+    {
+        let mut level: Vec<H::Domain> = leaves.iter().map(|data| hasher.hash_leaf(data)).collect();
+        while level.len() > 1 {
+            level = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => hasher.hash_inner(left.clone(), right.clone()),
+                    [node] => hasher.hash_inner(node.clone(), H::Domain::default()),
+                    _ => unreachable!(),
+                })
+                .collect();
+        }
+        level.pop().unwrap()
+    }
+
+    #[test]
+    fn poseidon_merkle_hasher_matches_merkle_root() {
+        let hasher = PoseidonMerkleHasher::<Fr, generic_array::typenum::U4>::new();
+        for num_leaves in [1, 2, 5, 8] {
+            let leaves: Vec<Vec<Fr>> = (0..num_leaves)
+                .map(|i| {
+                    (0..i % 6 + 1)
+                        .map(|j| Fr::from((i * 8 + j) as u64))
+                        .collect()
+                })
+                .collect();
+            let digests: Vec<Fr> = leaves.iter().map(|data| hasher.hash_leaf(data)).collect();
+
+            assert_eq!(
+                merkle_root(hasher.inner_constants(), &digests).unwrap(),
+                generic_root(&hasher, &leaves)
+            );
+        }
+    }
+
+    #[test]
+    fn compress_matches_generic_hash() {
+        fn check<F: PrimeField>(constants: &PoseidonConstants<F, U2>) {
+            for i in 0..8u64 {
+                let (left, right) = (F::from(i), F::from(i * i + 3) - F::ONE.double());
+                assert_eq!(
+                    Poseidon::new_with_preimage(&[left, right], constants).hash(),
+                    compress(constants, left, right)
+                );
+            }
+        }
+
+        check(&PoseidonConstants::<Fr, U2>::new());
+        check(&PoseidonConstants::<Fr, U2>::new_with_strength(
+            crate::Strength::Strengthened,
+        ));
+        check(&PoseidonConstants::<pasta_curves::Fp, U2>::new());
+
+        let standard = PoseidonConstants::<Fr, U2>::new();
+        let width = standard.width();
+        check(&PoseidonConstants::<Fr, U2>::from_parts(
+            standard.mds_matrices.m.clone(),
+            standard.round_constants.clone().unwrap()[..width * standard.full_rounds].to_vec(),
+            standard.full_rounds,
+            0,
+            crate::Strength::Standard,
+            crate::hash_type::HashType::MerkleTree,
+        ));
+    }
+
+    #[test]
+    fn merkle_root_small_trees() {
+        let constants = PoseidonConstants::<Fr, U2>::new();
+        let leaves: Vec<Fr> = (0..7u64).map(Fr::from).collect();
+        let h = |l, r| compress(&constants, l, r);
+
+        assert!(merkle_root(&constants, &[]).is_err());
+        assert_eq!(leaves[0], merkle_root(&constants, &leaves[..1]).unwrap());
+        assert_eq!(
+            h(h(leaves[0], leaves[1]), h(leaves[2], leaves[3])),
+            merkle_root(&constants, &leaves[..4]).unwrap()
+        );
+        // Odd levels are padded with zero.
+        assert_eq!(
+            h(h(leaves[0], leaves[1]), h(leaves[2], Fr::ZERO)),
+            merkle_root(&constants, &leaves[..3]).unwrap()
+        );
+        assert_eq!(
+            h(
+                h(h(leaves[0], leaves[1]), h(leaves[2], leaves[3])),
+                h(h(leaves[4], leaves[5]), h(leaves[6], Fr::ZERO))
+            ),
+            merkle_root(&constants, &leaves).unwrap()
+        );
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn par_merkle_root_matches_serial() {
+        let constants = PoseidonConstants::<Fr, U2>::new();
+        let leaves: Vec<Fr> = (0..1u64 << 14).map(Fr::from).collect();
+        assert_eq!(
+            merkle_root(&constants, &leaves).unwrap(),
+            par_merkle_root(&constants, &leaves).unwrap()
+        );
+
+        for n in [1, 2, 3, 1000] {
+            assert_eq!(
+                merkle_root(&constants, &leaves[..n]).unwrap(),
+                par_merkle_root(&constants, &leaves[..n]).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn batch_builder_matches_merkle_root() {
+        let constants = PoseidonConstants::<Fr, U2>::new();
+        let leaves: Vec<Fr> = (0..100u64).map(Fr::from).collect();
+
+        assert!(BatchMerkleBuilder::new(&constants).finalize().is_err());
+
+        for n in (1..=40).chain([63, 64, 65, 100]) {
+            let mut builder = BatchMerkleBuilder::new(&constants);
+            for leaf in &leaves[..n] {
+                builder.add_leaf(*leaf);
+                assert!(builder.peaks.len() <= usize::BITS as usize - n.leading_zeros() as usize);
+            }
+            assert_eq!(n, builder.leaf_count());
+            assert_eq!(
+                merkle_root(&constants, &leaves[..n]).unwrap(),
+                builder.finalize().unwrap(),
+                "wrong root for {} leaves",
+                n
+            );
+
+            for padding in [Padding::Promote, Padding::Element(Fr::from(u64::MAX))] {
+                let mut builder = BatchMerkleBuilder::new_with_padding(&constants, padding);
+                leaves[..n].iter().for_each(|leaf| builder.add_leaf(*leaf));
+                assert_eq!(
+                    merkle_root_with_padding(&constants, &leaves[..n], padding).unwrap(),
+                    builder.finalize().unwrap(),
+                    "wrong root for {} leaves with {:?}",
+                    n,
+                    padding
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn merkle_tree_layers() {
+        let constants = PoseidonConstants::<Fr, U2>::new();
+        let leaves: Vec<Fr> = (0..5u64).map(Fr::from).collect();
+        let h = |l, r| compress(&constants, l, r);
+
+        let tree = MerkleTree::build(&constants, &leaves).unwrap();
+        assert_eq!(4, tree.num_layers());
+        assert_eq!(Some(&leaves[..]), tree.layer(0));
+        assert_eq!(
+            Some(
+                &[
+                    h(leaves[0], leaves[1]),
+                    h(leaves[2], leaves[3]),
+                    h(leaves[4], Fr::ZERO)
+                ][..]
+            ),
+            tree.layer(1)
+        );
+        assert_eq!(Some(&[tree.root()][..]), tree.layer(3));
+        assert_eq!(None, tree.layer(4));
+        assert_eq!(merkle_root(&constants, &leaves).unwrap(), tree.root());
+
+        let promoted =
+            MerkleTree::build_with_options(&constants, &leaves, Padding::Promote, false).unwrap();
+        assert_eq!(
+            Some(&[h(leaves[0], leaves[1]), h(leaves[2], leaves[3]), leaves[4]][..]),
+            promoted.layer(1)
+        );
+        assert_eq!(Some(&[promoted.root()][..]), promoted.layer(3));
+
+        let discarded =
+            MerkleTree::build_with_options(&constants, &leaves, Padding::zero(), true).unwrap();
+        assert_eq!(tree.root(), discarded.root());
+        assert_eq!(4, discarded.num_layers());
+        assert_eq!(None, discarded.layer(0));
+
+        let single = MerkleTree::build(&constants, &leaves[..1]).unwrap();
+        assert_eq!(1, single.num_layers());
+        assert_eq!(leaves[0], single.root());
+        assert!(MerkleTree::build(&constants, &[]).is_err());
+    }
+
+    #[test]
+    fn all_proofs_verify() {
+        let constants = PoseidonConstants::<Fr, U2>::new();
+        let leaves: Vec<Fr> = (0..16u64).map(Fr::from).collect();
+
+        let tree = MerkleTree::build(&constants, &leaves).unwrap();
+        let proofs = tree.all_proofs();
+        assert_eq!(16, proofs.len());
+        for (index, proof) in proofs.iter().enumerate() {
+            assert_eq!(index, proof.index);
+            assert_eq!(4, proof.siblings.len());
+            assert!(proof.verify(&constants, tree.root()));
+            assert_eq!(Some(proof), tree.proof(index).as_ref());
+
+            let mut forged = proof.clone();
+            forged.leaf += Fr::ONE;
+            assert!(!forged.verify(&constants, tree.root()));
+        }
+        assert_eq!(None, tree.proof(16));
+
+        // Odd levels, with promoted and padded nodes.
+        for padding in [Padding::Promote, Padding::zero()] {
+            let tree =
+                MerkleTree::build_with_options(&constants, &leaves[..5], padding, false).unwrap();
+            for proof in tree.all_proofs() {
+                assert!(proof.verify(&constants, tree.root()));
+            }
+        }
+        let promoted =
+            MerkleTree::build_with_options(&constants, &leaves[..5], Padding::Promote, false)
+                .unwrap();
+        assert_eq!(
+            vec![None, None, Some(promoted.layer(2).unwrap()[0])],
+            promoted.all_proofs()[4].siblings
+        );
+
+        let discarded =
+            MerkleTree::build_with_options(&constants, &leaves, Padding::Promote, true).unwrap();
+        assert!(discarded.all_proofs().is_empty());
+        assert_eq!(None, discarded.proof(0));
+    }
+
+    #[test]
+    fn update_leaf() {
+        let constants = PoseidonConstants::<Fr, U2>::new();
+        let mut leaves: Vec<Fr> = (0..11u64).map(Fr::from).collect();
+
+        for padding in [Padding::Promote, Padding::zero()] {
+            let mut tree =
+                MerkleTree::build_with_options(&constants, &leaves, padding, false).unwrap();
+            for index in [0, 3, 9, 10] {
+                leaves[index] += Fr::from(100);
+                let root = tree.update_leaf(&constants, index, leaves[index]).unwrap();
+                let rebuilt =
+                    MerkleTree::build_with_options(&constants, &leaves, padding, false).unwrap();
+                assert_eq!(rebuilt.root(), root);
+                assert_eq!(rebuilt, tree);
+            }
+
+            assert_eq!(
+                tree.root(),
+                tree.update_leaf(&constants, 4, leaves[4]).unwrap()
+            );
+            assert!(matches!(
+                tree.update_leaf(&constants, 11, Fr::ONE),
+                Err(Error::IndexOutOfBounds)
+            ));
+        }
+
+        let mut single = MerkleTree::build(&constants, &leaves[..1]).unwrap();
+        assert_eq!(Fr::ONE, single.update_leaf(&constants, 0, Fr::ONE).unwrap());
+
+        let mut discarded =
+            MerkleTree::build_with_options(&constants, &leaves, Padding::Promote, true).unwrap();
+        assert!(discarded.update_leaf(&constants, 0, Fr::ONE).is_err());
+    }
+
+    #[test]
+    fn recompute_root_matches_tree() {
+        let constants = PoseidonConstants::<Fr, U2>::new();
+        let leaves: Vec<Fr> = (0..16u64).map(Fr::from).collect();
+        let h = |l, r| compress(&constants, l, r);
+
+        let tree = MerkleTree::build(&constants, &leaves).unwrap();
+        for index in [0, 1, 6, 11, 15] {
+            let path = tree.proof(index).unwrap().path();
+            assert_eq!(4, path.len());
+            assert_eq!(
+                tree.root(),
+                recompute_root(&constants, leaves[index], &path)
+            );
+        }
+
+        let path = [(leaves[0], true), (h(leaves[2], leaves[3]), false)];
+        assert_eq!(
+            h(h(leaves[0], leaves[1]), h(leaves[2], leaves[3])),
+            recompute_root(&constants, leaves[1], &path)
+        );
+        assert_eq!(leaves[3], recompute_root(&constants, leaves[3], &[]));
+
+        // The promoted last leaf of a tree of 5 has a single sibling.
+        let tree =
+            MerkleTree::build_with_options(&constants, &leaves[..5], Padding::Promote, false)
+                .unwrap();
+        let path = tree.proof(4).unwrap().path();
+        assert_eq!(1, path.len());
+        assert_eq!(tree.root(), recompute_root(&constants, leaves[4], &path));
+    }
+
+    #[test]
+    fn empty_subtree_digests_by_level() {
+        let constants = PoseidonConstants::<Fr, U2>::new();
+        let empty_leaf = Fr::from(0);
+        let digests = empty_subtree_digests(&constants, 8, empty_leaf);
+
+        assert_eq!(9, digests.len());
+        assert_eq!(empty_leaf, digests[0]);
+        for level in 1..digests.len() {
+            assert_eq!(
+                compress(&constants, digests[level - 1], digests[level - 1]),
+                digests[level]
+            );
+        }
+
+        // Level 3 is the root of a tree of 8 empty leaves.
+        assert_eq!(
+            merkle_root(&constants, &[empty_leaf; 8]).unwrap(),
+            digests[3]
+        );
+        assert_eq!(
+            vec![empty_leaf],
+            empty_subtree_digests(&constants, 0, empty_leaf)
+        );
+    }
+
+    #[test]
+    fn padding_affects_root() {
+        let constants = PoseidonConstants::<Fr, U2>::new();
+        let leaves = [Fr::from(1), Fr::from(2), Fr::from(3)];
+        let h = |l, r| compress(&constants, l, r);
+
+        let promoted = merkle_root_with_padding(&constants, &leaves, Padding::Promote).unwrap();
+        let zero_padded = merkle_root_with_padding(&constants, &leaves, Padding::zero()).unwrap();
+        let sentinel_padded =
+            merkle_root_with_padding(&constants, &leaves, Padding::Element(Fr::from(u64::MAX)))
+                .unwrap();
+
+        assert_eq!(h(h(leaves[0], leaves[1]), leaves[2]), promoted);
+        assert_eq!(
+            h(h(leaves[0], leaves[1]), h(leaves[2], Fr::ZERO)),
+            zero_padded
+        );
+        assert_eq!(merkle_root(&constants, &leaves).unwrap(), zero_padded);
+        assert_ne!(promoted, zero_padded);
+        assert_ne!(zero_padded, sentinel_padded);
+
+        // Padding is irrelevant when no level is odd.
+        assert_eq!(
+            merkle_root(&constants, &leaves[..2]).unwrap(),
+            merkle_root_with_padding(&constants, &leaves[..2], Padding::zero()).unwrap()
+        );
+    }
+}
@@ -0,0 +1,262 @@
+//! Constants for several arities, generated together.
+//!
+//! Trees which use one arity at the base and another above (for example, column trees) need [`PoseidonConstants`]
+//! for each. [`MultiArityConstants`] generates them once for every arity in [`SUPPORTED_ARITIES`], which matches the
+//! arities supported by the GPU kernels. Since the arity is part of the type of [`PoseidonConstants`], constants are
+//! looked up by type: `constants.get::<U8>()`. [`TreeShape`] describes such a tree, and generates constants for only
+//! the arities it uses.
+use crate::poseidon::{Arity, PoseidonConstants};
+use crate::{Error, Strength, DEFAULT_STRENGTH};
+use ff::PrimeField;
+use generic_array::typenum::{Unsigned, U11, U16, U2, U24, U36, U4, U8};
+
+/// The arities for which [`MultiArityConstants`] holds constants.
+pub const SUPPORTED_ARITIES: [usize; 7] = [2, 4, 8, 11, 16, 24, 36];
+
+/// Access to the constants of a single arity within [`MultiArityConstants`].
+pub trait ArityConstants<F: PrimeField, A: Arity<F>> {
+    /// Returns the constants for arity `A`, or `None` if they were not generated.
+    fn try_constants(&self) -> Option<&PoseidonConstants<F, A>>;
+
+    /// Returns the constants for arity `A`, panicking if they were not generated.
+    fn constants(&self) -> &PoseidonConstants<F, A> {
+        self.try_constants()
+            .unwrap_or_else(|| panic!("constants for arity {} were not generated", A::to_usize()))
+    }
+}
+
+macro_rules! multi_arity_constants {
+    ($($field:ident: $a:ty),*) => {
+        /// [`PoseidonConstants`] for each of the [`SUPPORTED_ARITIES`], or a subset of them, all of the same
+        /// [`Strength`].
+        #[derive(Clone, Debug, PartialEq)]
+        pub struct MultiArityConstants<F: PrimeField> {
+            $($field: Option<PoseidonConstants<F, $a>>,)*
+        }
+
+        impl<F: PrimeField> MultiArityConstants<F> {
+            pub fn new_with_strength(strength: Strength) -> Self {
+                Self {
+                    $($field: Some(PoseidonConstants::new_with_strength(strength)),)*
+                }
+            }
+
+            /// Generates constants for only the given arities. Returns [`Error::Other`] if any of them is not one of
+            /// the [`SUPPORTED_ARITIES`].
+            pub fn new_for_arities(strength: Strength, arities: &[usize]) -> Result<Self, Error> {
+                if let Some(arity) = arities.iter().find(|a| !SUPPORTED_ARITIES.contains(a)) {
+                    return Err(Error::Other(format!("unsupported arity {arity}")));
+                }
+
+                Ok(Self {
+                    $($field: arities
+                        .contains(&<$a>::to_usize())
+                        .then(|| PoseidonConstants::new_with_strength(strength)),)*
+                })
+            }
+
+            /// Returns the arities for which constants were generated, in increasing order.
+            pub fn arities(&self) -> Vec<usize> {
+                let mut arities = Vec::new();
+                $(
+                    if self.$field.is_some() {
+                        arities.push(<$a>::to_usize());
+                    }
+                )*
+                arities
+            }
+        }
+
+        $(
+            impl<F: PrimeField> ArityConstants<F, $a> for MultiArityConstants<F> {
+                fn try_constants(&self) -> Option<&PoseidonConstants<F, $a>> {
+                    self.$field.as_ref()
+                }
+            }
+        )*
+    };
+}
+
+multi_arity_constants!(
+    arity2: U2,
+    arity4: U4,
+    arity8: U8,
+    arity11: U11,
+    arity16: U16,
+    arity24: U24,
+    arity36: U36
+);
+
+impl<F: PrimeField> MultiArityConstants<F> {
+    pub fn new() -> Self {
+        Self::new_with_strength(DEFAULT_STRENGTH)
+    }
+
+    /// Returns the constants for arity `A`, which must be one of the [`SUPPORTED_ARITIES`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the constants for arity `A` were not generated; see [`MultiArityConstants::try_get`].
+    pub fn get<A: Arity<F>>(&self) -> &PoseidonConstants<F, A>
+    where
+        Self: ArityConstants<F, A>,
+    {
+        <Self as ArityConstants<F, A>>::constants(self)
+    }
+
+    /// Returns the constants for arity `A`, or `None` if they were not generated.
+    pub fn try_get<A: Arity<F>>(&self) -> Option<&PoseidonConstants<F, A>>
+    where
+        Self: ArityConstants<F, A>,
+    {
+        <Self as ArityConstants<F, A>>::try_constants(self)
+    }
+}
+
+impl<F: PrimeField> Default for MultiArityConstants<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The shape of a tree with one arity at the base and another above, such as a column tree: the first of its `depth`
+/// levels of hashing uses `base_arity` and the others use `upper_arity`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TreeShape {
+    base_arity: usize,
+    upper_arity: usize,
+    depth: usize,
+}
+
+impl TreeShape {
+    /// Returns [`Error::Other`] if an arity is less than 2 or not one of the [`SUPPORTED_ARITIES`], or if `depth` is
+    /// zero.
+    pub fn new(base_arity: usize, upper_arity: usize, depth: usize) -> Result<Self, Error> {
+        for arity in [base_arity, upper_arity] {
+            if arity < 2 || !SUPPORTED_ARITIES.contains(&arity) {
+                return Err(Error::Other(format!("unsupported tree arity {arity}")));
+            }
+        }
+        if depth == 0 {
+            return Err(Error::Other("tree depth must be at least 1".to_string()));
+        }
+
+        Ok(Self {
+            base_arity,
+            upper_arity,
+            depth,
+        })
+    }
+
+    pub fn base_arity(&self) -> usize {
+        self.base_arity
+    }
+
+    pub fn upper_arity(&self) -> usize {
+        self.upper_arity
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns the number of leaves of a full tree of this shape, or `None` on overflow.
+    pub fn num_leaves(&self) -> Option<usize> {
+        (1..self.depth).try_fold(self.base_arity, |leaves, _| {
+            leaves.checked_mul(self.upper_arity)
+        })
+    }
+
+    /// Returns the arities used by the tree, in increasing order. The upper arity is only used by trees of depth 2 or
+    /// more.
+    pub fn arities(&self) -> Vec<usize> {
+        let mut arities = vec![self.base_arity];
+        if self.depth > 1 && self.upper_arity != self.base_arity {
+            arities.push(self.upper_arity);
+        }
+        arities.sort_unstable();
+        arities
+    }
+
+    /// Generates the constants for exactly the arities used by the tree.
+    pub fn constants<F: PrimeField>(&self) -> MultiArityConstants<F> {
+        self.constants_with_strength(DEFAULT_STRENGTH)
+    }
+
+    pub fn constants_with_strength<F: PrimeField>(
+        &self,
+        strength: Strength,
+    ) -> MultiArityConstants<F> {
+        MultiArityConstants::new_for_arities(strength, &self.arities())
+            .expect("arities were validated on construction")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Poseidon;
+    use blstrs::Scalar as Fr;
+    use generic_array::typenum::Unsigned;
+
+    /// Hashes `leaves` into the next row of the tree, `A` elements at a time.
+    fn reduce<A: Arity<Fr>>(leaves: Vec<Fr>, constants: &PoseidonConstants<Fr, A>) -> Vec<Fr> {
+        leaves
+            .chunks(A::to_usize())
+            .map(|chunk| Poseidon::new_with_preimage(chunk, constants).hash())
+            .collect()
+    }
+
+    #[test]
+    fn mixed_arity_tree() {
+        let multi = MultiArityConstants::<Fr>::new();
+        for (arity, expected) in [
+            (multi.get::<U2>().arity(), 2),
+            (multi.get::<U8>().arity(), 8),
+            (multi.get::<U36>().arity(), 36),
+        ] {
+            assert_eq!(expected, arity);
+            assert!(SUPPORTED_ARITIES.contains(&arity));
+        }
+
+        // 64 leaves: one arity-8 row down to 8 nodes, then arity 2 up to the root.
+        let leaves: Vec<Fr> = (0..64u64).map(Fr::from).collect();
+
+        let mut row = reduce(leaves.clone(), multi.get::<U8>());
+        while row.len() > 1 {
+            row = reduce(row, multi.get::<U2>());
+        }
+
+        let base = PoseidonConstants::<Fr, U8>::new();
+        let above = PoseidonConstants::<Fr, U2>::new();
+        let mut expected = reduce(leaves, &base);
+        while expected.len() > 1 {
+            expected = reduce(expected, &above);
+        }
+
+        assert_eq!(expected, row);
+    }
+
+    #[test]
+    fn tree_shape_constants() {
+        let shape = TreeShape::new(8, 2, 4).unwrap();
+        assert_eq!(Some(64), shape.num_leaves());
+        assert_eq!(vec![2, 8], shape.arities());
+
+        let constants = shape.constants::<Fr>();
+        assert_eq!(vec![2, 8], constants.arities());
+        assert_eq!(&PoseidonConstants::<Fr, U8>::new(), constants.get::<U8>());
+        assert_eq!(&PoseidonConstants::<Fr, U2>::new(), constants.get::<U2>());
+        assert!(constants.try_get::<U4>().is_none());
+
+        // A single level only hashes with the base arity.
+        assert_eq!(vec![4], TreeShape::new(4, 2, 1).unwrap().arities());
+        assert_eq!(vec![8], TreeShape::new(8, 8, 3).unwrap().arities());
+
+        assert!(TreeShape::new(1, 2, 2).is_err());
+        assert!(TreeShape::new(8, 0, 2).is_err());
+        assert!(TreeShape::new(8, 3, 2).is_err());
+        assert!(TreeShape::new(8, 2, 0).is_err());
+        assert!(MultiArityConstants::<Fr>::new_for_arities(Strength::Standard, &[2, 5]).is_err());
+    }
+}
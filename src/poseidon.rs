@@ -1,18 +1,29 @@
 use crate::hash_type::HashType;
 use crate::matrix::Matrix;
-use crate::mds::{create_mds_matrices, factor_to_sparse_matrixes, MdsMatrices, SparseMatrix};
+use crate::mds::{
+    create_mds_matrices, derive_mds_matrices, try_factor_to_sparse_matrixes, MdsMatrices,
+};
 use crate::poseidon_alt::{hash_correct, hash_optimized_dynamic};
 use crate::preprocessing::compress_round_constants;
+use crate::sponge::vanilla::{Mode, Sponge, SpongeTrait};
 use crate::{matrix, quintic_s_box, BatchHasher, Strength, DEFAULT_STRENGTH};
 use crate::{round_constants, round_numbers, Error};
 use ff::PrimeField;
 use generic_array::{sequence::GenericSequence, typenum, ArrayLength, GenericArray};
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::marker::PhantomData;
 use typenum::marker_traits::Unsigned;
 use typenum::*;
 
+pub use crate::mds::{MdsSource, SparseMatrix};
+
 /// Available arities for the Poseidon hasher.
+///
+/// Arity 1 (`U1`) hashes a single element, with a state of width 2: the domain tag and the element. Its round numbers
+/// are derived by the same security analysis as for other widths, and its single capacity element provides the same
+/// collision resistance as other arities. However, each permutation absorbs only one element, so it is by far the
+/// least efficient arity for hashing more than one element, which is better done with a wider arity or a sponge.
 pub trait Arity<T>: ArrayLength<T> {
     /// Must be Arity + 1.
     type ConstantsSize: ArrayLength<T>;
@@ -27,13 +38,50 @@ macro_rules! impl_arity {
                 type ConstantsSize = Add1<$a>;
 
                 fn tag() -> F {
-                    F::from((1 << <$a as Unsigned>::to_usize()) - 1)
+                    arity_tag(<$a as Unsigned>::to_usize())
                 }
             }
         )*
     };
 }
 
+/// The largest arity [`Arity`] is implemented for. Wider states grow the MDS matrices quadratically and the round
+/// constants linearly, for ever smaller gains in throughput.
+pub const MAX_ARITY: usize = 36;
+
+/// Returns the largest arity for which constants can be generated for `F`: [`MAX_ARITY`], or less for fields of fewer
+/// than `MAX_ARITY + 1` bits, whose Merkle tree domain tags (see [`arity_tag`]) would not be distinct for all arities.
+pub fn max_arity<F: PrimeField>() -> usize {
+    MAX_ARITY.min(F::NUM_BITS as usize - 1)
+}
+
+/// Returns the Merkle tree domain tag `2^arity - 1` of `arity`. The tag is computed in the field, so it does not
+/// overflow for any arity; it is distinct for all arities below `F::NUM_BITS`. [`Arity`] is implemented for arities up
+/// to 36.
+pub fn arity_tag<F: PrimeField>(arity: usize) -> F {
+    F::from(2).pow_vartime([arity as u64]) - F::ONE
+}
+
+/// Returns the Merkle tree domain tag of `arity` as a `u64`, or `None` if it does not fit, that is, for arities above
+/// 64.
+pub fn arity_tag_u64(arity: usize) -> Option<u64> {
+    match arity {
+        0..=63 => Some((1 << arity) - 1),
+        64 => Some(u64::MAX),
+        _ => None,
+    }
+}
+
+/// Returns the number of inputs of [`Arity`] `A` as a `usize`.
+pub fn arity_count<F: PrimeField, A: Arity<F>>() -> usize {
+    A::to_usize()
+}
+
+/// Returns the width of the permutation state for [`Arity`] `A` as a `usize`: the arity plus one for the domain tag.
+pub fn arity_width<F: PrimeField, A: Arity<F>>() -> usize {
+    A::ConstantsSize::to_usize()
+}
+
 // Dummy implementation to allow for an "optional" argument.
 impl<F: PrimeField> Arity<F> for U0 {
     type ConstantsSize = U0;
@@ -70,6 +118,16 @@ where
     _f: PhantomData<F>,
 }
 
+/// The transient state of a [`Poseidon`] hasher, without its constants, for checkpointing a hash in progress. A saved
+/// state is only meaningful together with the constants it was saved with, which must be stored separately.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoseidonState<F: PrimeField> {
+    pub elements: Vec<F>,
+    pub constants_offset: usize,
+    pub current_round: usize,
+    pub pos: usize,
+}
+
 /// Holds constant values required for further [`Poseidon`] hashing. It contains MDS matrices,
 /// round constants and numbers, parameters that specify security level ([`Strength`]) and
 /// domain separation ([`HashType`]). Additional constants related to optimizations are also included.
@@ -143,6 +201,52 @@ where
         Self::new_with_strength(DEFAULT_STRENGTH)
     }
 
+    /// Like [`PoseidonConstants::new`], but returns an error instead of panicking if constants cannot be generated
+    /// for the field, as for fields too small for the width. See
+    /// [`PoseidonConstants::try_new_with_strength_and_type`].
+    pub fn try_new() -> Result<Self, Error> {
+        Self::try_new_with_strength_and_type(DEFAULT_STRENGTH, HashType::MerkleTree)
+    }
+
+    /// Like [`PoseidonConstants::new`], but runs all self-checks of [`PoseidonConstants::validate`] before returning
+    /// the constants. [`PoseidonConstants::new`] trusts the constant generation and performs none of these checks.
+    pub fn new_validated() -> Result<Self, Error> {
+        let constants = Self::new();
+        constants.validate()?;
+        Ok(constants)
+    }
+
+    /// Like [`PoseidonConstants::new`], but keeps only what [`Poseidon::hash`] needs: of the MDS matrices only `m` is
+    /// kept, and `m_inv`, `m_hat`, `m_hat_inv`, `m_prime` and `m_double_prime` are left empty; the uncompressed
+    /// `round_constants` are `None`. The omitted fields are still computed, since the compressed round constants and
+    /// sparse matrices are derived from them, so construction is no faster, but the constants take less memory and
+    /// serialize to fewer bytes.
+    ///
+    /// The constants produce the same digests as [`PoseidonConstants::new`] in the default hash mode. Like constants
+    /// from [`PoseidonConstants::deserialize_hashing_only`], they must not be used with other hash modes or to derive
+    /// further constants.
+    pub fn new_minimal() -> Self {
+        let constants = Self::new();
+        Self {
+            mds_matrices: MdsMatrices::only_m(constants.mds_matrices.m),
+            round_constants: None,
+            ..constants
+        }
+    }
+
+    /// Like [`PoseidonConstants::new`], but uses `tag` as the domain tag instead of the one derived from the arity
+    /// and [`HashType`]. Everything else is generated as usual.
+    ///
+    /// Digests are not compatible with those of any standard neptune hash type, and `hash_type` no longer determines
+    /// the tag. The serde implementations rederive the tag from `hash_type`, so it must be set again after loading
+    /// such constants, unless they are stored with [`crate::versioned::write_constants`], which keeps the tag.
+    pub fn new_with_domain_tag(tag: F) -> Self {
+        Self {
+            domain_tag: tag,
+            ..Self::new()
+        }
+    }
+
     /// Generates new instance of [`PoseidonConstants`] suitable for both optimized / non-optimized hashing
     /// of constant-size preimages with following parameters:
     /// - 128 bit of security;
@@ -199,7 +303,7 @@ where
             _ => panic!("cannot set constant length of hash without type ConstantLength."),
         };
 
-        let domain_tag = hash_type.domain_tag();
+        let domain_tag = hash_type.domain_tag_with_strength(self.strength);
 
         Self {
             hash_type,
@@ -252,24 +356,147 @@ where
     pub fn new_with_strength_and_type(strength: Strength, hash_type: HashType<F, A>) -> Self {
         assert!(hash_type.is_supported());
         let arity = A::to_usize();
+        let max = max_arity::<F>();
+        assert!(arity <= max, "arity {arity} exceeds the maximum of {max}");
         let width = arity + 1;
 
         let mds_matrices = create_mds_matrices(width);
 
         let (full_rounds, partial_rounds) = round_numbers(arity, &strength);
-        let half_full_rounds = full_rounds / 2;
         let round_constants = round_constants(arity, &strength);
-        let compressed_round_constants = compress_round_constants(
-            width,
+
+        Self::from_mds_matrices(
+            mds_matrices,
+            round_constants,
             full_rounds,
             partial_rounds,
-            &round_constants,
-            &mds_matrices,
+            strength,
+            hash_type,
+        )
+    }
+
+    /// Like [`PoseidonConstants::new_with_strength_and_type`], but returns an error instead of panicking:
+    /// [`Error::MdsGenerationFailed`] if no MDS matrix or sparse factorization exists for the field and width (the
+    /// Cauchy construction needs the field to be larger than twice the width), and [`Error::Other`] if `hash_type` is
+    /// not supported or the field elements are not 32 bytes long, as round constant generation requires.
+    pub fn try_new_with_strength_and_type(
+        strength: Strength,
+        hash_type: HashType<F, A>,
+    ) -> Result<Self, Error> {
+        Self::try_new_with_mds_source(strength, hash_type, &MdsSource::Cauchy)
+    }
+
+    /// Like [`PoseidonConstants::try_new_with_strength_and_type`], but takes the MDS matrix from `mds_source`, for
+    /// instance to reproduce the matrix of another implementation. The round constants are generated as usual, and the
+    /// sparse matrices and compressed round constants are derived from the chosen matrix. With
+    /// [`MdsSource::Cauchy`], the default, the constants are those of
+    /// [`PoseidonConstants::try_new_with_strength_and_type`].
+    pub fn try_new_with_mds_source(
+        strength: Strength,
+        hash_type: HashType<F, A>,
+        mds_source: &MdsSource<F>,
+    ) -> Result<Self, Error> {
+        if !hash_type.is_supported() {
+            return Err(Error::Other(format!("unsupported hash type {hash_type:?}")));
+        }
+        let arity = A::to_usize();
+        let max = max_arity::<F>();
+        if arity > max {
+            return Err(Error::ArityTooLarge { max });
+        }
+        let width = arity + 1;
+
+        let mds_matrices = mds_source.try_mds_matrices(width)?;
+        let (full_rounds, partial_rounds) = round_numbers(arity, &strength);
+
+        let repr_len = F::Repr::default().as_ref().len();
+        if repr_len != 32 {
+            return Err(Error::Other(format!(
+                "round constants can only be generated for 32-byte fields, not {repr_len}-byte fields"
+            )));
+        }
+        let round_constants = round_constants(arity, &strength);
+
+        Self::try_from_mds_matrices(
+            mds_matrices,
+            round_constants,
+            full_rounds,
             partial_rounds,
+            strength,
+            hash_type,
+        )
+    }
+
+    /// Creates new instance of [`PoseidonConstants`] from externally supplied parameters: an MDS matrix,
+    /// the (uncompressed) round constants, and the round numbers. All constants needed for optimized hashing
+    /// are derived from these.
+    ///
+    /// The MDS matrix is applied as a row vector times matrix product (`state * mds`), so matrices taken from
+    /// implementations which compute `mds * state` must be transposed first.
+    ///
+    /// `strength` is recorded as-is and is not used to derive anything.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `mds` is not an invertible matrix of size [`Arity`] + 1, if there are not enough round
+    /// constants for the requested rounds, or if `hash_type` is not supported.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use neptune::poseidon::PoseidonConstants;
+    /// use neptune::Strength;
+    /// use neptune::hash_type::HashType;
+    /// use pasta_curves::Fp;
+    /// use generic_array::typenum::U2;
+    ///
+    /// let constants: PoseidonConstants<Fp, U2> = PoseidonConstants::new();
+    /// let imported: PoseidonConstants<Fp, U2> = PoseidonConstants::from_parts(
+    ///     constants.mds_matrices.m.clone(),
+    ///     constants.round_constants.clone().unwrap(),
+    ///     constants.full_rounds,
+    ///     constants.partial_rounds,
+    ///     Strength::Standard,
+    ///     HashType::MerkleTree,
+    /// );
+    ///
+    /// assert_eq!(constants, imported);
+    /// ```
+    pub fn from_parts(
+        mds: Matrix<F>,
+        round_constants: Vec<F>,
+        full_rounds: usize,
+        partial_rounds: usize,
+        strength: Strength,
+        hash_type: HashType<F, A>,
+    ) -> Self {
+        assert!(hash_type.is_supported());
+        let width = A::ConstantsSize::to_usize();
+        assert!(
+            matrix::is_square(&mds) && matrix::rows(&mds) == width,
+            "MDS matrix must be square of size {}",
+            width
         );
 
-        let (pre_sparse_matrix, sparse_matrixes) =
-            factor_to_sparse_matrixes(mds_matrices.m.clone(), partial_rounds);
+        Self::from_mds_matrices(
+            derive_mds_matrices(mds),
+            round_constants,
+            full_rounds,
+            partial_rounds,
+            strength,
+            hash_type,
+        )
+    }
+
+    fn from_mds_matrices(
+        mds_matrices: MdsMatrices<F>,
+        round_constants: Vec<F>,
+        full_rounds: usize,
+        partial_rounds: usize,
+        strength: Strength,
+        hash_type: HashType<F, A>,
+    ) -> Self {
+        let width = A::ConstantsSize::to_usize();
 
         // Ensure we have enough constants for the sbox rounds
         assert!(
@@ -277,25 +504,67 @@ where
             "Not enough round constants"
         );
 
+        Self::try_from_mds_matrices(
+            mds_matrices,
+            round_constants,
+            full_rounds,
+            partial_rounds,
+            strength,
+            hash_type,
+        )
+        .unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    fn try_from_mds_matrices(
+        mds_matrices: MdsMatrices<F>,
+        round_constants: Vec<F>,
+        full_rounds: usize,
+        partial_rounds: usize,
+        strength: Strength,
+        hash_type: HashType<F, A>,
+    ) -> Result<Self, Error> {
+        let width = A::ConstantsSize::to_usize();
+        let half_full_rounds = full_rounds / 2;
+
+        let expected = width * (full_rounds + partial_rounds);
+        if round_constants.len() < expected {
+            return Err(Error::InsufficientRoundConstants {
+                got: round_constants.len(),
+                expected,
+            });
+        }
+
+        let compressed_round_constants = compress_round_constants(
+            width,
+            full_rounds,
+            partial_rounds,
+            &round_constants,
+            &mds_matrices,
+            partial_rounds,
+        );
+
+        let (pre_sparse_matrix, sparse_matrixes) =
+            try_factor_to_sparse_matrixes(mds_matrices.m.clone(), partial_rounds)?;
+
         assert_eq!(
             full_rounds * width + partial_rounds,
             compressed_round_constants.len()
         );
 
-        Self {
+        Ok(Self {
             mds_matrices,
             round_constants: Some(round_constants),
             compressed_round_constants,
             pre_sparse_matrix,
             sparse_matrixes,
             strength,
-            domain_tag: hash_type.domain_tag(),
+            domain_tag: hash_type.domain_tag_with_strength(strength),
             full_rounds,
             half_full_rounds,
             partial_rounds,
             hash_type,
             _a: PhantomData::<A>,
-        }
+        })
     }
 
     /// Returns the [`Arity`] value represented as `usize`.
@@ -333,6 +602,325 @@ where
     pub fn width(&self) -> usize {
         A::ConstantsSize::to_usize()
     }
+
+    /// Returns the domain tag which initializes the first element of the state, as derived from the hash type and
+    /// strength (see [`HashType::domain_tag_with_strength`]).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use neptune::poseidon::PoseidonConstants;
+    /// use neptune::hash_type::HashType;
+    /// use pasta_curves::Fp;
+    /// use generic_array::typenum::U4;
+    ///
+    /// let constants: PoseidonConstants<Fp, U4> = PoseidonConstants::new_constant_length(3);
+    ///
+    /// assert_eq!(constants.domain_tag(), HashType::<Fp, U4>::ConstantLength(3).domain_tag());
+    /// ```
+    #[inline]
+    pub fn domain_tag(&self) -> F {
+        self.domain_tag
+    }
+
+    /// Cheap sanity check of the round constants, intended to be run after loading constants from an untrusted or
+    /// possibly corrupted source. Returns [`Error::InvalidRoundConstants`] if either the round constants (when present)
+    /// or the compressed round constants contain more than [`SUSPICIOUS_ZERO_COUNT`] zeros, or a run of
+    /// [`SUSPICIOUS_RUN_LENGTH`] or more identical consecutive constants. Neither happens for properly generated
+    /// constants, and either weakens the hash.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use neptune::poseidon::PoseidonConstants;
+    /// use pasta_curves::Fp;
+    /// use ff::Field;
+    /// use generic_array::typenum::U2;
+    ///
+    /// let mut constants: PoseidonConstants<Fp, U2> = PoseidonConstants::new();
+    /// assert!(constants.sanity_check_round_constants().is_ok());
+    ///
+    /// constants.compressed_round_constants.iter_mut().for_each(|c| *c = Fp::ZERO);
+    /// assert!(constants.sanity_check_round_constants().is_err());
+    /// ```
+    pub fn sanity_check_round_constants(&self) -> Result<(), Error> {
+        if let Some(round_constants) = &self.round_constants {
+            sanity_check_constants("round constants", round_constants)?;
+        }
+        sanity_check_constants(
+            "compressed round constants",
+            &self.compressed_round_constants,
+        )
+    }
+
+    /// Returns [`Error::InsufficientRoundConstants`] if the round constants (when present) or the compressed round
+    /// constants are shorter than the round schedule requires. Hashing with such constants would otherwise fail with
+    /// an opaque index out of bounds panic, or silently skip round constants.
+    pub fn check_round_constants_len(&self) -> Result<(), Error> {
+        let width = self.width();
+        let check = |got: usize, expected: usize| {
+            if got < expected {
+                Err(Error::InsufficientRoundConstants { got, expected })
+            } else {
+                Ok(())
+            }
+        };
+
+        if let Some(round_constants) = &self.round_constants {
+            check(
+                round_constants.len(),
+                (self.full_rounds + self.partial_rounds) * width,
+            )?;
+        }
+        check(
+            self.compressed_round_constants.len(),
+            self.full_rounds * width + self.partial_rounds,
+        )
+    }
+
+    /// Runs all self-checks, returning the first failure. This is expensive, as it recomputes all derived matrices and
+    /// constants, and is meant for constants from untrusted sources or created with
+    /// [`PoseidonConstants::from_parts`]. The checks are:
+    /// - [`PoseidonConstants::check_round_constants_len`] and [`PoseidonConstants::sanity_check_round_constants`];
+    /// - the MDS matrix and its minor used by the optimizations are invertible, and the derived MDS matrices and
+    ///   sparse factorization are those of the MDS matrix;
+    /// - when the uncompressed round constants are present, they compress to the compressed round constants, and the
+    ///   optimized and reference permutations agree on a fixed preimage.
+    ///
+    /// Failures of the round constants are reported as [`Error::InvalidRoundConstants`] or
+    /// [`Error::InsufficientRoundConstants`], MDS matrices without a sparse factorization as
+    /// [`Error::MdsGenerationFailed`], and inconsistent matrices as [`Error::Other`].
+    pub fn validate(&self) -> Result<(), Error> {
+        self.check_round_constants_len()?;
+        self.sanity_check_round_constants()?;
+
+        let mds = &self.mds_matrices.m;
+        let width = self.width();
+        if !(matrix::is_square(mds) && matrix::rows(mds) == width) {
+            return Err(Error::Other(format!(
+                "MDS matrix must be square of size {width}"
+            )));
+        }
+        if !matrix::is_invertible(mds) || !matrix::is_invertible(&matrix::minor(mds, 0, 0)) {
+            return Err(Error::Other(
+                "MDS matrix or its minor is not invertible".to_string(),
+            ));
+        }
+        if self.mds_matrices != derive_mds_matrices(mds.clone()) {
+            return Err(Error::Other(
+                "derived MDS matrices do not match the MDS matrix".to_string(),
+            ));
+        }
+        let (pre_sparse_matrix, sparse_matrixes) =
+            try_factor_to_sparse_matrixes(mds.clone(), self.partial_rounds)?;
+        if pre_sparse_matrix != self.pre_sparse_matrix || sparse_matrixes != self.sparse_matrixes {
+            return Err(Error::Other(
+                "sparse matrices do not match the MDS matrix".to_string(),
+            ));
+        }
+
+        if let Some(round_constants) = &self.round_constants {
+            let compressed = compress_round_constants(
+                width,
+                self.full_rounds,
+                self.partial_rounds,
+                round_constants,
+                &self.mds_matrices,
+                self.partial_rounds,
+            );
+            if compressed != self.compressed_round_constants {
+                return Err(Error::InvalidRoundConstants(
+                    "compressed round constants do not match the round constants".to_string(),
+                ));
+            }
+
+            let preimage: Vec<F> = (1..=self.arity() as u64).map(F::from).collect();
+            let mut optimized = Poseidon::new(self);
+            optimized.set_preimage(&preimage);
+            let mut reference = Poseidon::new(self);
+            reference.set_preimage(&preimage);
+            if optimized.hash_in_mode(OptimizedStatic) != reference.hash_in_mode(Correct) {
+                return Err(Error::Other(
+                    "optimized and reference permutations disagree".to_string(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the [`HashMode`] expected to be fastest for these constants, as used by [`Poseidon::hash_auto`]. The
+    /// estimate counts the multiplications of the linear layers, which is where the modes differ: the reference
+    /// permutation multiplies by the dense MDS matrix in every round, while the optimized permutation multiplies by
+    /// sparse matrices (`2 * width - 1` multiplications) in partial rounds, at the price of one extra dense
+    /// multiplication. [`HashMode::Correct`] is only chosen when the uncompressed round constants are available, and
+    /// is always chosen if they are but the optimized permutation is not [supported](Self::supports_optimized).
+    ///
+    /// For all arities and round numbers generated by `neptune` this is [`HashMode::OptimizedStatic`]; the `hash`
+    /// benchmarks compare both modes.
+    pub fn preferred_hash_mode(&self) -> HashMode {
+        let width = self.width();
+        let dense = width * width;
+        let correct = (self.full_rounds + self.partial_rounds) * dense;
+        let optimized = (self.full_rounds + 1) * dense + self.partial_rounds * (2 * width - 1);
+
+        if (correct < optimized || !self.supports_optimized()) && self.round_constants.is_some() {
+            Correct
+        } else {
+            OptimizedStatic
+        }
+    }
+
+    /// Returns whether these constants hold the preprocessed data of the optimized permutation used by
+    /// [`Poseidon::hash`]: the compressed round constants, the pre-sparse matrix and one sparse matrix per partial
+    /// round, all of the right sizes. This holds for constants built by `neptune`, but not necessarily for constants
+    /// assembled or imported field by field, which [`Poseidon::hash`] would panic on. [`Poseidon::try_hash`] checks it.
+    pub fn supports_optimized(&self) -> bool {
+        let width = self.width();
+        self.half_full_rounds > 0
+            && self.compressed_round_constants.len()
+                == self.full_rounds * width + self.partial_rounds
+            && self.pre_sparse_matrix.len() == width
+            && self.pre_sparse_matrix.iter().all(|row| row.len() == width)
+            && self.sparse_matrixes.len() >= self.partial_rounds
+            && self.sparse_matrixes[..self.partial_rounds]
+                .iter()
+                .all(|sparse| sparse.w_hat.len() == width && sparse.v_rest.len() == width - 1)
+    }
+
+    /// Returns the bit length of the field modulus, `F::NUM_BITS`, which seeds the generation of the round constants.
+    /// The round numbers do not depend on it: they are computed for a 256-bit modulus, which is conservative for all
+    /// fields of at most 256 bits.
+    pub fn field_modulus_bits(&self) -> u32 {
+        F::NUM_BITS
+    }
+
+    /// Returns a 32-byte Blake2s fingerprint of everything that determines the digests: the field, the arity, the
+    /// domain tag, the round numbers, the MDS matrix, the sparse matrices and the compressed round constants. Two
+    /// deployments hash identically if their fingerprints match. The uncompressed round constants are not included,
+    /// since they are only used by the correct and dynamic modes and are derived from the same parameters.
+    pub fn fingerprint(&self) -> [u8; 32] {
+        let mut state = blake2s_simd::Params::new().hash_length(32).to_state();
+        state.update(F::MODULUS.as_bytes());
+        for n in [
+            self.arity(),
+            self.full_rounds,
+            self.partial_rounds,
+            self.compressed_round_constants.len(),
+            self.sparse_matrixes.len(),
+        ] {
+            state.update(&(n as u64).to_le_bytes());
+        }
+        state.update(self.domain_tag.to_repr().as_ref());
+        let elements = self
+            .mds_matrices
+            .m
+            .iter()
+            .chain(&self.pre_sparse_matrix)
+            .flatten()
+            .chain(&self.compressed_round_constants)
+            .chain(
+                self.sparse_matrixes
+                    .iter()
+                    .flat_map(|sparse| sparse.w_hat.iter().chain(&sparse.v_rest)),
+            );
+        for elt in elements {
+            state.update(elt.to_repr().as_ref());
+        }
+
+        let mut fingerprint = [0u8; 32];
+        fingerprint.copy_from_slice(state.finalize().as_bytes());
+        fingerprint
+    }
+
+    /// Estimates the number of multiplication constraints a circuit computing one permutation with these constants
+    /// needs, for planning R1CS or Plonk circuits. Each quintic S-box takes three multiplications (`x^2`, `x^4` and
+    /// `x^5`), and there are `width` S-boxes per full round and one per partial round. Round constants and the linear
+    /// layers are assumed to fold into linear combinations at no cost, as in R1CS.
+    ///
+    /// This is the count of [`crate::circuit2::poseidon_hash_allocated`] without its output constraint. Circuits
+    /// which exploit constant inputs, such as the domain tag, need a few fewer.
+    pub fn estimate_constraints(&self) -> usize {
+        3 * (self.width() * self.full_rounds + self.partial_rounds)
+    }
+
+    /// Returns the MDS matrix as a nested `Vec`, row by row, for export to external tooling. Note that `neptune`
+    /// multiplies the state as a row vector by this matrix (`state * M`); since generated matrices are symmetric, this
+    /// only matters for matrices imported with [`PoseidonConstants::from_parts`].
+    pub fn mds_dense(&self) -> Vec<Vec<F>> {
+        self.mds_matrices.m.clone()
+    }
+
+    /// Returns the uncompressed round constants, `width` per round, for export to external tooling.
+    ///
+    /// Constants loaded from a serialized form hold only the compressed round constants, and compression cannot be
+    /// inverted in general. In that case the round constants are regenerated for the width and round numbers of
+    /// `self`, and returned only if they compress to the stored compressed round constants. Returns
+    /// [`Error::InvalidRoundConstants`] otherwise, as happens for constants originally imported with custom round
+    /// constants.
+    pub fn round_constants_dense(&self) -> Result<Vec<F>, Error> {
+        if let Some(round_constants) = &self.round_constants {
+            return Ok(round_constants.clone());
+        }
+
+        let round_constants = crate::round_constants::generate_constants::<F>(
+            crate::FIELD,
+            crate::SBOX,
+            F::NUM_BITS as u16,
+            self.width() as u16,
+            self.full_rounds as u16,
+            self.partial_rounds as u16,
+        );
+        let compressed = compress_round_constants(
+            self.width(),
+            self.full_rounds,
+            self.partial_rounds,
+            &round_constants,
+            &self.mds_matrices,
+            self.partial_rounds,
+        );
+
+        if compressed == self.compressed_round_constants {
+            Ok(round_constants)
+        } else {
+            Err(Error::InvalidRoundConstants(
+                "compressed round constants are not those of the standard generation and cannot be decompressed"
+                    .to_string(),
+            ))
+        }
+    }
+}
+
+/// Number of zero round constants above which [`PoseidonConstants::sanity_check_round_constants`] fails.
+pub const SUSPICIOUS_ZERO_COUNT: usize = 1;
+
+/// Length of a run of identical round constants at which [`PoseidonConstants::sanity_check_round_constants`] fails.
+pub const SUSPICIOUS_RUN_LENGTH: usize = 3;
+
+fn sanity_check_constants<F: PrimeField>(name: &str, constants: &[F]) -> Result<(), Error> {
+    let zeros = constants.iter().filter(|c| c.is_zero_vartime()).count();
+    if zeros > SUSPICIOUS_ZERO_COUNT {
+        return Err(Error::InvalidRoundConstants(format!(
+            "{name} contain {zeros} zeros"
+        )));
+    }
+
+    let mut run = 1;
+    for (i, pair) in constants.windows(2).enumerate() {
+        if pair[0] == pair[1] {
+            run += 1;
+            if run >= SUSPICIOUS_RUN_LENGTH {
+                return Err(Error::InvalidRoundConstants(format!(
+                    "{name} repeat the same value {run} times, ending at index {}",
+                    i + 1
+                )));
+            }
+        } else {
+            run = 1;
+        }
+    }
+
+    Ok(())
 }
 
 impl<F, A> Default for PoseidonConstants<F, A>
@@ -345,6 +933,37 @@ where
     }
 }
 
+/// A compact, single-line summary suitable for logs. The MDS matrix and round constants are summarized by a short
+/// digest; use [`Debug`](fmt::Debug) to see them in full.
+impl<F, A> fmt::Display for PoseidonConstants<F, A>
+where
+    F: PrimeField,
+    A: Arity<F>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut state = blake2s_simd::Params::new().hash_length(4).to_state();
+        for row in &self.mds_matrices.m {
+            for elt in row {
+                state.update(elt.to_repr().as_ref());
+            }
+        }
+        for elt in &self.compressed_round_constants {
+            state.update(elt.to_repr().as_ref());
+        }
+
+        write!(
+            f,
+            "PoseidonConstants {{ arity: {}, strength: {}, full_rounds: {}, partial_rounds: {}, hash_type: {:?}, digest: {} }}",
+            self.arity(),
+            self.strength,
+            self.full_rounds,
+            self.partial_rounds,
+            self.hash_type,
+            state.finalize().to_hex()
+        )
+    }
+}
+
 impl<'a, F, A> Poseidon<'a, F, A>
 where
     F: PrimeField,
@@ -494,6 +1113,71 @@ where
         self.pos = self.elements.len();
     }
 
+    /// Replaces the elements with the provided items, like [`Poseidon::set_preimage`], but returns
+    /// [`Error::InvalidPreimageLength`] instead of panicking if the slice is not equal to the arity.
+    /// The state is left untouched on error.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use neptune::poseidon::PoseidonConstants;
+    /// use neptune::poseidon::Poseidon;
+    /// use neptune::Error;
+    /// use pasta_curves::Fp;
+    /// use ff::Field;
+    /// use generic_array::typenum::U2;
+    ///
+    /// let constants: PoseidonConstants<Fp, U2> = PoseidonConstants::new();
+    /// let mut poseidon = Poseidon::<Fp, U2>::new(&constants);
+    ///
+    /// assert!(poseidon.set_preimage_slice(&[Fp::ONE, Fp::ONE]).is_ok());
+    /// assert!(matches!(
+    ///     poseidon.set_preimage_slice(&[Fp::ONE]),
+    ///     Err(Error::InvalidPreimageLength { got: 1, expected: 2 })
+    /// ));
+    /// ```
+    pub fn set_preimage_slice(&mut self, preimage: &[F]) -> Result<(), Error> {
+        let expected = self.constants.arity();
+        if preimage.len() != expected {
+            return Err(Error::InvalidPreimageLength {
+                got: preimage.len(),
+                expected,
+            });
+        }
+        self.set_preimage(preimage);
+        Ok(())
+    }
+
+    /// Returns the current state, for restoring with [`Poseidon::load_state`].
+    pub fn save_state(&self) -> PoseidonState<F> {
+        PoseidonState {
+            elements: self.elements.to_vec(),
+            constants_offset: self.constants_offset,
+            current_round: self.current_round,
+            pos: self.pos,
+        }
+    }
+
+    /// Restores a state returned by [`Poseidon::save_state`] of a hasher with the same constants. Returns
+    /// [`Error::Other`], leaving `self` untouched, if the state does not fit the width of the constants.
+    pub fn load_state(&mut self, state: &PoseidonState<F>) -> Result<(), Error> {
+        let width = self.constants.width();
+        if state.elements.len() != width || state.pos > width {
+            return Err(Error::Other(format!(
+                "state of {} elements at position {} does not fit width {}",
+                state.elements.len(),
+                state.pos,
+                width
+            )));
+        }
+
+        self.elements.copy_from_slice(&state.elements);
+        self.constants_offset = state.constants_offset;
+        self.current_round = state.current_round;
+        self.pos = state.pos;
+        Ok(())
+    }
+
     /// Restore the initial state
     pub fn reset(&mut self) {
         self.reset_offsets();
@@ -584,14 +1268,49 @@ where
             OptimizedDynamic => hash_optimized_dynamic(self),
             OptimizedStatic => self.hash_optimized_static(),
         };
+        self.debug_check_constants_offset(&mode);
         self.reset_offsets();
         res
     }
 
+    /// Checks that hashing in `mode` consumed exactly the round constants required by the round schedule. This
+    /// catches off-by-one errors in the round loops, as well as round constants too short for the schedule.
+    fn debug_check_constants_offset(&self, mode: &HashMode) {
+        let width = self.constants.width();
+        let (expected, available) = match mode {
+            Correct | OptimizedDynamic => (
+                (self.constants.full_rounds + self.constants.partial_rounds) * width,
+                self.constants.round_constants.as_ref().map_or(0, Vec::len),
+            ),
+            // Partial rounds consume a single compressed round constant.
+            OptimizedStatic => (
+                self.constants.full_rounds * width + self.constants.partial_rounds,
+                self.constants.compressed_round_constants.len(),
+            ),
+        };
+
+        debug_assert_eq!(
+            expected, self.constants_offset,
+            "Round constants consumed ({}) do not match the round schedule ({}).",
+            self.constants_offset, expected
+        );
+        debug_assert!(
+            expected <= available,
+            "Round schedule requires {} round constants, but only {} are provided.",
+            expected,
+            available
+        );
+    }
+
     /// Performs hashing using underlying [`Poseidon`] buffer of the preimage' field elements
     /// in default (optimized) mode. Always outputs digest expressed as a single field element
     /// of concrete type specified upon [`PoseidonConstants`] and [`Poseidon`] instantiations.
     ///
+    /// An all-zero preimage is hashed like any other. Although the S-box maps zero to zero, the state always includes
+    /// the nonzero domain tag (for Merkle trees, `2^arity - 1`) and round constants are added in every round, so its
+    /// digest is not zero. Its digest is nevertheless a useful test vector, and the tests record it per arity, for
+    /// detecting inputs which are accidentally zero.
+    ///
     /// # Example
     ///
     /// ```
@@ -615,18 +1334,150 @@ where
         self.hash_in_mode(DEFAULT_HASH_MODE)
     }
 
-    pub(crate) fn apply_padding(&mut self) {
-        if let HashType::ConstantLength(l) = self.constants.hash_type {
-            let final_pos = 1 + (l % self.constants.arity());
+    /// Hashes in the mode of [`PoseidonConstants::preferred_hash_mode`]. All modes compute the same permutation, so
+    /// the digest is that of [`Poseidon::hash`].
+    pub fn hash_auto(&mut self) -> F {
+        self.hash_in_mode(self.constants.preferred_hash_mode())
+    }
 
-            assert_eq!(
-                self.pos, final_pos,
-                "preimage length does not match constant length required for hash"
-            );
-        };
-        match self.constants.hash_type {
-            HashType::ConstantLength(_) | HashType::Encryption => {
-                for elt in self.elements[self.pos..].iter_mut() {
+    /// Hashes like [`Poseidon::hash`] if the constants [support](PoseidonConstants::supports_optimized) the optimized
+    /// permutation, and otherwise in [`HashMode::Correct`], which gives the same digest. Returns an error instead of
+    /// panicking if neither is possible, because the constants also lack the uncompressed round constants.
+    pub fn try_hash(&mut self) -> Result<F, Error> {
+        if self.constants.supports_optimized() {
+            Ok(self.hash())
+        } else if self.constants.round_constants.is_some() {
+            Ok(self.hash_in_mode(Correct))
+        } else {
+            Err(Error::Other(
+                "constants have neither the optimized data nor the round constants".to_string(),
+            ))
+        }
+    }
+
+    /// Hashes like [`Poseidon::hash`], writing the digest to `out`, such as a slot of a preallocated output vector.
+    pub fn hash_into(&mut self, out: &mut F) {
+        *out = self.hash();
+    }
+
+    /// Hashes like [`Poseidon::hash`], consuming the hasher. After hashing, the elements hold the permuted state rather
+    /// than a preimage, so hashing again without [`Poseidon::reset`] or [`Poseidon::set_preimage`] is almost always a
+    /// mistake; consuming the hasher rules it out.
+    pub fn finish(mut self) -> F {
+        self.hash()
+    }
+
+    /// Hashes like [`Poseidon::hash`], but returns `n` output elements: the first is the digest [`Poseidon::hash`]
+    /// returns, and each further element is `elements[1]` after permuting the state again.
+    ///
+    /// A single element digest has about `F::NUM_BITS / 2` bits of collision resistance, which for fields smaller
+    /// than 256 bits is below 128 bits. Squeezing more elements lengthens the output, so that attacks on the output
+    /// alone (such as birthday collisions on the digest) become infeasible. Note however that the capacity of the
+    /// sponge remains a single element, so generic attacks on the internal state are still bounded by about
+    /// `F::NUM_BITS / 2` bits; wide digests do not raise security beyond the capacity bound.
+    pub fn hash_wide(&mut self, n: usize) -> Vec<F> {
+        (0..n).map(|_| self.hash()).collect()
+    }
+
+    /// Returns the offset of the next (uncompressed) round constant used by [`Poseidon::run_full_rounds`] and
+    /// [`Poseidon::run_partial_rounds`].
+    pub fn constants_offset(&self) -> usize {
+        self.constants_offset
+    }
+
+    /// Sets the offset of the next round constant, for running custom round schedules. Returns
+    /// [`Error::IndexOutOfBounds`] if `offset` is past the end of the round constants.
+    pub fn set_constants_offset(&mut self, offset: usize) -> Result<(), Error> {
+        if offset > self.uncompressed_round_constants()?.len() {
+            return Err(Error::IndexOutOfBounds);
+        }
+        self.constants_offset = offset;
+        Ok(())
+    }
+
+    /// Runs `n` full rounds of the reference permutation (as in [`HashMode::Correct`]): each adds the next `width`
+    /// round constants, applies the S-box to every element and multiplies by the MDS matrix. Together with
+    /// [`Poseidon::run_partial_rounds`] and [`Poseidon::set_constants_offset`], this allows composing custom round
+    /// schedules. [`Poseidon::hash`] corresponds to `half_full_rounds` full rounds, `partial_rounds` partial rounds and
+    /// `half_full_rounds` full rounds from offset zero, after which the digest is in `elements[1]`.
+    ///
+    /// Returns [`Error::InsufficientRoundConstants`], without running any round, if fewer than `n * width` round
+    /// constants remain.
+    pub fn run_full_rounds(&mut self, n: usize) -> Result<(), Error> {
+        self.check_remaining_round_constants(n * self.elements.len())?;
+        for _ in 0..n {
+            crate::poseidon_alt::full_round(self);
+        }
+        Ok(())
+    }
+
+    /// Runs `n` partial rounds of the reference permutation: as full rounds, except that the S-box is only applied to
+    /// the first element. See [`Poseidon::run_full_rounds`].
+    pub fn run_partial_rounds(&mut self, n: usize) -> Result<(), Error> {
+        self.check_remaining_round_constants(n * self.elements.len())?;
+        for _ in 0..n {
+            crate::poseidon_alt::partial_round(self);
+        }
+        Ok(())
+    }
+
+    /// Hashes like [`Poseidon::hash`] with the reference permutation, recording the state after each round, for
+    /// analyzing diffusion. The trace has `full_rounds + partial_rounds` entries, and the digest is element 1 of the
+    /// last. The states of the optimized permutations differ from these by linear transformations, so only the
+    /// reference permutation is traced.
+    ///
+    /// Returns [`Error::InvalidRoundConstants`] if the constants lack the uncompressed round constants, as
+    /// deserialized constants do.
+    #[cfg(feature = "trace")]
+    pub fn hash_trace(&mut self) -> Result<Vec<GenericArray<F, A::ConstantsSize>>, Error> {
+        let constants = self.constants;
+        let rounds = constants.full_rounds + constants.partial_rounds;
+        self.check_remaining_round_constants(rounds * self.elements.len())?;
+
+        let partial =
+            constants.half_full_rounds..constants.half_full_rounds + constants.partial_rounds;
+        let mut trace = Vec::with_capacity(rounds);
+        for round in 0..rounds {
+            if partial.contains(&round) {
+                crate::poseidon_alt::partial_round(self);
+            } else {
+                crate::poseidon_alt::full_round(self);
+            }
+            trace.push(self.elements.clone());
+        }
+        self.reset_offsets();
+        Ok(trace)
+    }
+
+    fn uncompressed_round_constants(&self) -> Result<&[F], Error> {
+        self.constants.round_constants.as_deref().ok_or_else(|| {
+            Error::InvalidRoundConstants(
+                "uncompressed round constants are not available".to_string(),
+            )
+        })
+    }
+
+    fn check_remaining_round_constants(&self, needed: usize) -> Result<(), Error> {
+        let got = self.uncompressed_round_constants()?.len();
+        let expected = self.constants_offset + needed;
+        if got < expected {
+            return Err(Error::InsufficientRoundConstants { got, expected });
+        }
+        Ok(())
+    }
+
+    pub(crate) fn apply_padding(&mut self) {
+        if let HashType::ConstantLength(l) = self.constants.hash_type {
+            let final_pos = 1 + (l % self.constants.arity());
+
+            assert_eq!(
+                self.pos, final_pos,
+                "preimage length does not match constant length required for hash"
+            );
+        };
+        match self.constants.hash_type {
+            HashType::ConstantLength(_) | HashType::Encryption => {
+                for elt in self.elements[self.pos..].iter_mut() {
                     *elt = F::ZERO;
                 }
                 self.pos = self.elements.len();
@@ -697,6 +1548,81 @@ where
     /// assert_ne!(digest, Fp::ZERO); // digest has `Fp` type
     /// ```
     pub fn hash_optimized_static(&mut self) -> F {
+        let mut scratch = GenericArray::<F, A::ConstantsSize>::generate(|_| F::ZERO);
+        self.hash_optimized_static_with_scratch(&mut scratch)
+    }
+
+    /// Like [`Poseidon::hash`], but uses `scratch` to hold the results of the dense MDS products, so that callers
+    /// hashing in a tight loop can reuse one buffer across calls. The contents of `scratch` on entry are irrelevant
+    /// and on return are unspecified. The digest is the same as that of [`Poseidon::hash`].
+    pub fn hash_with_scratch(&mut self, scratch: &mut GenericArray<F, A::ConstantsSize>) -> F {
+        let res = self.hash_optimized_static_with_scratch(scratch);
+        self.debug_check_constants_offset(&OptimizedStatic);
+        self.reset_offsets();
+        res
+    }
+
+    fn hash_optimized_static_with_scratch(
+        &mut self,
+        scratch: &mut GenericArray<F, A::ConstantsSize>,
+    ) -> F {
+        let mut rounds = StaticRounds {
+            constants: self.constants,
+            elements: &mut self.elements,
+            scratch,
+            constants_offset: self.constants_offset,
+            current_round: self.current_round,
+        };
+        rounds.permute();
+        self.constants_offset = rounds.constants_offset;
+        self.current_round = rounds.current_round;
+
+        self.extract_output()
+    }
+
+    /// Set the provided elements with the result of the product between the elements and the constant
+    /// MDS matrix.
+    pub(crate) fn product_mds(&mut self) {
+        self.product_mds_with_matrix(&self.constants.mds_matrices.m);
+    }
+
+    /// NOTE: This calculates a vector-matrix product (`elements * matrix`) rather than the
+    /// expected matrix-vector `(matrix * elements)`. This is a performance optimization which
+    /// exploits the fact that our MDS matrices are symmetric by construction.
+    ///
+    /// The product is accumulated row by row, so that each row of the matrix is read contiguously. Since field
+    /// addition is exact, the order of accumulation does not affect the result.
+    #[allow(clippy::ptr_arg)]
+    pub(crate) fn product_mds_with_matrix(&mut self, matrix: &Matrix<F>) {
+        let mut result = GenericArray::<F, A::ConstantsSize>::generate(|_| F::ZERO);
+        product_mds_with_matrix_into(&mut self.elements, matrix, &mut result);
+    }
+
+    pub(crate) fn debug(&self, msg: &str) {
+        dbg!(msg, &self.constants_offset, &self.elements);
+    }
+}
+
+/// The rounds of [`HashMode::OptimizedStatic`], run over a borrowed state, so that the permutation needs no
+/// [`Poseidon`]. `constants_offset` and `current_round` track the position in the round schedule.
+struct StaticRounds<'a, 'b, F, A>
+where
+    F: PrimeField,
+    A: Arity<F>,
+{
+    constants: &'a PoseidonConstants<F, A>,
+    elements: &'b mut GenericArray<F, A::ConstantsSize>,
+    scratch: &'b mut GenericArray<F, A::ConstantsSize>,
+    constants_offset: usize,
+    current_round: usize,
+}
+
+impl<F, A> StaticRounds<'_, '_, F, A>
+where
+    F: PrimeField,
+    A: Arity<F>,
+{
+    fn permute(&mut self) {
         // The first full round should use the initial constants.
         self.add_round_constants();
 
@@ -721,8 +1647,6 @@ where
             self.constants_offset,
             self.constants.compressed_round_constants.len()
         );
-
-        self.extract_output()
     }
 
     fn full_round(&mut self, last_round: bool) {
@@ -780,16 +1704,17 @@ where
         self.round_product_mds();
     }
 
+    /// Adds the next `width` compressed round constants to the state. The constants are stored as `F`, that is, in
+    /// whatever internal representation the field backend uses (Montgomery form for `blstrs` and `pasta_curves`), so
+    /// the addition is a plain modular addition with no conversion.
     fn add_round_constants(&mut self) {
-        for (element, round_constant) in self.elements.iter_mut().zip(
-            self.constants
-                .compressed_round_constants
-                .iter()
-                .skip(self.constants_offset),
-        ) {
+        let width = self.elements.len();
+        let round_constants = &self.constants.compressed_round_constants
+            [self.constants_offset..self.constants_offset + width];
+        for (element, round_constant) in self.elements.iter_mut().zip(round_constants) {
             element.add_assign(round_constant);
         }
-        self.constants_offset += self.elements.len();
+        self.constants_offset += width;
     }
 
     /// Set the provided elements with the result of the product between the elements and the appropriate
@@ -799,74 +1724,391 @@ where
         let full_half = self.constants.half_full_rounds;
         let sparse_offset = full_half - 1;
         if self.current_round == sparse_offset {
-            self.product_mds_with_matrix(&self.constants.pre_sparse_matrix);
+            product_mds_with_matrix_into(
+                self.elements,
+                &self.constants.pre_sparse_matrix,
+                self.scratch,
+            );
         } else {
             if (self.current_round > sparse_offset)
                 && (self.current_round < full_half + self.constants.partial_rounds)
             {
                 let index = self.current_round - sparse_offset - 1;
-                let sparse_matrix = &self.constants.sparse_matrixes[index];
-
-                self.product_mds_with_sparse_matrix(sparse_matrix);
+                // Sparse matrix in this context means one of the form, M''.
+                self.constants.sparse_matrixes[index].apply_unchecked(self.elements);
             } else {
-                self.product_mds();
+                product_mds_with_matrix_into(
+                    self.elements,
+                    &self.constants.mds_matrices.m,
+                    self.scratch,
+                );
             }
         };
 
         self.current_round += 1;
     }
+}
 
-    /// Set the provided elements with the result of the product between the elements and the constant
-    /// MDS matrix.
-    pub(crate) fn product_mds(&mut self) {
-        self.product_mds_with_matrix(&self.constants.mds_matrices.m);
+/// Computes the product of `elements` with `matrix` (see [`Poseidon::product_mds_with_matrix`]) in `scratch`, which
+/// is then swapped with the elements. Widths 5 and 9, of the arities 4 and 8 common in column trees, use a product
+/// over fixed-size arrays, which the compiler unrolls.
+#[allow(clippy::ptr_arg)]
+fn product_mds_with_matrix_into<F: PrimeField>(
+    elements: &mut [F],
+    matrix: &Matrix<F>,
+    scratch: &mut [F],
+) {
+    match elements.len() {
+        5 => dense_product_fixed::<F, 5>(elements, matrix, scratch),
+        9 => dense_product_fixed::<F, 9>(elements, matrix, scratch),
+        _ => dense_product(elements, matrix, scratch),
     }
 
-    /// NOTE: This calculates a vector-matrix product (`elements * matrix`) rather than the
-    /// expected matrix-vector `(matrix * elements)`. This is a performance optimization which
-    /// exploits the fact that our MDS matrices are symmetric by construction.
-    #[allow(clippy::ptr_arg)]
-    pub(crate) fn product_mds_with_matrix(&mut self, matrix: &Matrix<F>) {
-        let mut result = GenericArray::<F, A::ConstantsSize>::generate(|_| F::ZERO);
+    elements.swap_with_slice(scratch);
+}
 
-        for (j, val) in result.iter_mut().enumerate() {
-            for (i, row) in matrix.iter().enumerate() {
-                let mut tmp = row[j];
-                tmp.mul_assign(&self.elements[i]);
-                val.add_assign(&tmp);
-            }
+/// Computes the vector-matrix product `elements * matrix` into `out`, accumulating row by row.
+#[allow(clippy::ptr_arg)]
+fn dense_product<F: PrimeField>(elements: &[F], matrix: &Matrix<F>, out: &mut [F]) {
+    out.iter_mut().for_each(|val| *val = F::ZERO);
+
+    for (element, row) in elements.iter().zip(matrix.iter()) {
+        for (val, entry) in out.iter_mut().zip(row.iter()) {
+            let mut tmp = *entry;
+            tmp.mul_assign(element);
+            val.add_assign(&tmp);
         }
+    }
+}
 
-        let _ = std::mem::replace(&mut self.elements, result);
+/// Like [`dense_product`], for a width known at compile time. The fixed-size arrays let the compiler unroll both loops
+/// and drop the bounds checks. The result is the same, since field addition is exact.
+#[allow(clippy::ptr_arg)]
+fn dense_product_fixed<F: PrimeField, const N: usize>(
+    elements: &[F],
+    matrix: &Matrix<F>,
+    out: &mut [F],
+) {
+    let elements: &[F; N] = elements.try_into().expect("state has width N");
+    let mut acc = [F::ZERO; N];
+    for (element, row) in elements.iter().zip(matrix.iter()) {
+        let row: &[F; N] = row.as_slice().try_into().expect("matrix has width N");
+        for (val, entry) in acc.iter_mut().zip(row) {
+            *val += *entry * element;
+        }
     }
+    out.copy_from_slice(&acc);
+}
 
-    // Sparse matrix in this context means one of the form, M''.
-    fn product_mds_with_sparse_matrix(&mut self, sparse_matrix: &SparseMatrix<F>) {
-        let mut result = GenericArray::<F, A::ConstantsSize>::generate(|_| F::ZERO);
+/// Hashes the elements of `iter` with constant-length domain separation ([`HashType::ConstantLength`]), taking the
+/// length from the iterator itself. Only the arity, strength and round constants of `constants` are used; its hash
+/// type is ignored.
+///
+/// Preimages no longer than [`Arity`] are input directly into a single permutation. Longer preimages are absorbed by
+/// a simplex sponge, which requires cloning `constants` with the appropriate domain tag. In neither case is the
+/// preimage collected first.
+///
+/// Returns [`Error::InvalidPreimageLength`] if the iterator does not yield exactly as many elements as it reports.
+///
+/// # Example
+///
+/// ```
+/// use neptune::poseidon::{hash_exact, Poseidon, PoseidonConstants};
+/// use pasta_curves::Fp;
+/// use generic_array::typenum::U4;
+///
+/// let constants: PoseidonConstants<Fp, U4> = PoseidonConstants::new();
+/// let preimage = vec![Fp::from(1), Fp::from(2), Fp::from(3)];
+///
+/// let digest = hash_exact(&constants, preimage.iter().copied()).unwrap();
+///
+/// let constant_length = PoseidonConstants::<Fp, U4>::new_constant_length(preimage.len());
+/// assert_eq!(digest, Poseidon::new_with_preimage(&preimage, &constant_length).hash());
+/// ```
+pub fn hash_exact<F, A, I>(constants: &PoseidonConstants<F, A>, iter: I) -> Result<F, Error>
+where
+    F: PrimeField,
+    A: Arity<F>,
+    I: ExactSizeIterator<Item = F>,
+{
+    hash_exact_with_tag_offset(constants, iter, F::ZERO)
+}
+
+/// Like [`hash_exact`], but with `tag_offset` added to the constant-length domain tag.
+fn hash_exact_with_tag_offset<F, A, I>(
+    constants: &PoseidonConstants<F, A>,
+    iter: I,
+    tag_offset: F,
+) -> Result<F, Error>
+where
+    F: PrimeField,
+    A: Arity<F>,
+    I: ExactSizeIterator<Item = F>,
+{
+    let length = iter.len();
+    let hash_type = HashType::<F, A>::ConstantLength(length);
+    let domain_tag = hash_type.domain_tag_with_strength(constants.strength) + tag_offset;
+    let mut got = 0;
+
+    let length_error = |got| Error::InvalidPreimageLength {
+        got,
+        expected: length,
+    };
 
-        // First column is dense.
-        for (i, val) in sparse_matrix.w_hat.iter().enumerate() {
-            let mut tmp = *val;
-            tmp.mul_assign(&self.elements[i]);
-            result[0].add_assign(&tmp);
+    if length <= A::to_usize() {
+        let mut p = Poseidon::new(constants);
+        p.elements[0] = domain_tag;
+        for element in iter {
+            if got < length {
+                p.input(element)?;
+            }
+            got += 1;
+        }
+        if got != length {
+            return Err(length_error(got));
+        }
+        Ok(p.hash())
+    } else {
+        let constants = PoseidonConstants {
+            domain_tag,
+            hash_type,
+            ..constants.clone()
+        };
+        let mut sponge = Sponge::new_with_constants(&constants, Mode::Simplex);
+        for element in iter {
+            if got < length {
+                sponge.absorb(&element, &mut ())?;
+            }
+            got += 1;
         }
+        if got != length {
+            return Err(length_error(got));
+        }
+        Ok(sponge
+            .squeeze(&mut ())?
+            .expect("simplex sponge always yields a digest"))
+    }
+}
+
+/// **Non-standard.** Hashes `preimage` with the first element of the state set to zero instead of the domain tag of
+/// `constants`, as some external implementations do. Such digests are not domain-separated and are not
+/// `neptune` digests: this exists only to reproduce the outputs of those implementations for interop testing and
+/// debugging, and is only available with the `nonstandard-interop` feature.
+///
+/// # Panics
+///
+/// Panics if the length of `preimage` is not [`Arity`].
+#[cfg(feature = "nonstandard-interop")]
+pub fn hash_no_tag<F, A>(constants: &PoseidonConstants<F, A>, preimage: &[F]) -> F
+where
+    F: PrimeField,
+    A: Arity<F>,
+{
+    assert_eq!(preimage.len(), A::to_usize(), "Invalid preimage size");
+    let mut p = Poseidon::new(constants);
+    p.elements[0] = F::ZERO;
+    p.elements[1..].copy_from_slice(preimage);
+    p.hash()
+}
 
-        for (j, val) in result.iter_mut().enumerate().skip(1) {
-            // Except for first row/column, diagonals are one.
-            val.add_assign(&self.elements[j]);
+/// Hashes `preimage` with `salt` added to the domain tag in the first element of the state, so that the same preimage
+/// hashed for different records gives unrelated digests. A zero salt gives the digest of
+/// [`Poseidon::new_with_preimage`] and [`Poseidon::hash`].
+///
+/// Salts provide separation between records hashed with the same `constants`, not between hash types: a salt equal to
+/// the difference of two domain tags makes salted digests of one hash type coincide with unsalted digests of the
+/// other. Salts should therefore be random, or derived from record identifiers by hashing, rather than small or
+/// attacker-chosen values.
+///
+/// # Panics
+///
+/// Panics if the length of `preimage` is invalid for the hash type, as [`Poseidon::new_with_preimage`] does.
+pub fn hash_salted<F, A>(constants: &PoseidonConstants<F, A>, salt: F, preimage: &[F]) -> F
+where
+    F: PrimeField,
+    A: Arity<F>,
+{
+    let mut p = Poseidon::new_with_preimage(preimage, constants);
+    p.elements[0] += salt;
+    p.hash()
+}
 
-            // First row is dense.
-            let mut tmp = sparse_matrix.v_rest[j - 1];
-            tmp.mul_assign(&self.elements[0]);
-            val.add_assign(&tmp);
-        }
+/// Runs the permutation on `state` in place and returns the digest, `state[1]`. The caller is responsible for the whole
+/// state, including the domain tag in `state[0]` (for example `constants.domain_tag()`) and any padding; with those set
+/// as [`Poseidon`] sets them, the digest equals that of [`Poseidon::hash`]. Afterwards, `state` holds the permuted
+/// state. The rounds run directly over `state`, without a [`Poseidon`] or a copy of the state.
+pub fn hash_in_place<F, A>(
+    constants: &PoseidonConstants<F, A>,
+    state: &mut GenericArray<F, A::ConstantsSize>,
+) -> F
+where
+    F: PrimeField,
+    A: Arity<F>,
+{
+    let mut scratch = GenericArray::<F, A::ConstantsSize>::generate(|_| F::ZERO);
+    StaticRounds {
+        constants,
+        elements: state,
+        scratch: &mut scratch,
+        constants_offset: 0,
+        current_round: 0,
+    }
+    .permute();
+    state[1]
+}
+
+/// Commits to a list of digests. Up to [`Arity`] digests are hashed directly with [`hash_exact`]. More digests are
+/// committed to by a tree of arity [`Arity`]: each group of up to [`Arity`] consecutive digests is hashed with
+/// [`hash_exact`], and the resulting row is reduced in the same way until at most [`Arity`] nodes remain, whose
+/// [`hash_exact`] digest is the root. Since the constant-length domain tag encodes the number of children, a final
+/// partial group needs no padding.
+///
+/// As with any Merkle tree, the root of a list of digests equals the commitment to the row of nodes below it, so the
+/// number of digests must be fixed by the context of the commitment.
+///
+/// Returns an error if `digests` is empty.
+pub fn hash_digests<F, A>(constants: &PoseidonConstants<F, A>, digests: &[F]) -> Result<F, Error>
+where
+    F: PrimeField,
+    A: Arity<F>,
+{
+    if digests.is_empty() {
+        return Err(Error::Other(
+            "cannot hash an empty list of digests".to_string(),
+        ));
+    }
 
-        let _ = std::mem::replace(&mut self.elements, result);
+    let arity = A::to_usize();
+    let mut row = digests.to_vec();
+    while row.len() > arity {
+        row = row
+            .chunks(arity)
+            .map(|group| hash_exact(constants, group.iter().copied()))
+            .collect::<Result<Vec<F>, Error>>()?;
     }
+    hash_exact(constants, row.into_iter())
+}
 
-    pub(crate) fn debug(&self, msg: &str) {
-        dbg!(msg, &self.constants_offset, &self.elements);
+/// Commits to a matrix, such as a lookup table, given as a slice of rows. Each row is hashed with [`hash_exact`], and
+/// the row digests are hashed like [`hash_exact`] but with `2^128` added to the domain tag. Constant-length tags are
+/// below `2^128`, so no row digest is the commitment of a matrix. Since each digest binds the length of its preimage,
+/// the commitment binds the number of rows and columns, and a matrix and its transpose have different commitments.
+///
+/// Returns an error if the matrix has no rows, if its rows are empty, or if they differ in length.
+pub fn hash_matrix<F, A>(constants: &PoseidonConstants<F, A>, rows: &[Vec<F>]) -> Result<F, Error>
+where
+    F: PrimeField,
+    A: Arity<F>,
+{
+    let columns = match rows.first() {
+        Some(row) if !row.is_empty() => row.len(),
+        _ => return Err(Error::Other("cannot hash an empty matrix".to_string())),
+    };
+    if let Some((i, row)) = rows
+        .iter()
+        .enumerate()
+        .find(|(_, row)| row.len() != columns)
+    {
+        return Err(Error::Other(format!(
+            "row {i} has {} elements, but row 0 has {columns}",
+            row.len()
+        )));
+    }
+
+    let digests = rows
+        .iter()
+        .map(|row| hash_exact(constants, row.iter().copied()))
+        .collect::<Result<Vec<F>, Error>>()?;
+    hash_exact_with_tag_offset(
+        constants,
+        digests.into_iter(),
+        F::from(2).pow_vartime([128]),
+    )
+}
+
+/// Hashes a preimage of any non-zero length. Preimages of up to [`Arity`] elements are hashed with [`hash_exact`].
+/// Longer preimages are folded by a tree of arity [`Arity`] as in [`hash_digests`], except that the root is hashed
+/// like [`hash_exact`] with `2^129 + length * 2^130` added to the domain tag, where `length` is the length of the
+/// preimage. The tag thus binds the length, so a preimage and its prefixes, or the row of nodes below its root, have
+/// different digests. The offset is at least `2^129`, so roots never coincide with [`hash_exact`] or [`hash_matrix`]
+/// digests.
+///
+/// Returns an error if `preimage` is empty.
+pub fn hash_any_length<F, A>(
+    constants: &PoseidonConstants<F, A>,
+    preimage: &[F],
+) -> Result<F, Error>
+where
+    F: PrimeField,
+    A: Arity<F>,
+{
+    if preimage.is_empty() {
+        return Err(Error::Other("cannot hash an empty preimage".to_string()));
+    }
+
+    let arity = A::to_usize();
+    if preimage.len() <= arity {
+        return hash_exact(constants, preimage.iter().copied());
     }
+
+    let fold = |nodes: &[F]| {
+        nodes
+            .chunks(arity)
+            .map(|group| hash_exact(constants, group.iter().copied()))
+            .collect::<Result<Vec<F>, Error>>()
+    };
+    let mut row = fold(preimage)?;
+    while row.len() > arity {
+        row = fold(&row)?;
+    }
+
+    let two = F::from(2);
+    let tag_offset =
+        two.pow_vartime([129]) + F::from(preimage.len() as u64) * two.pow_vartime([130]);
+    hash_exact_with_tag_offset(constants, row.into_iter(), tag_offset)
+}
+
+/// Returns the canonical digest of the empty preimage, such as a commitment to the empty set: the permutation of the
+/// state holding only a domain tag, with no input. The tag is that of [`hash_exact`] for length zero plus `2^131`,
+/// which no other hash type or helper of this crate uses. In particular, the empty digest differs from the digest of
+/// a preimage of zeros, which [`Poseidon::hash`] would otherwise produce for a state with no input.
+///
+/// Only the arity, strength and round constants of `constants` are used.
+pub fn empty_digest<F, A>(constants: &PoseidonConstants<F, A>) -> F
+where
+    F: PrimeField,
+    A: Arity<F>,
+{
+    hash_exact_with_tag_offset(constants, std::iter::empty(), F::from(2).pow_vartime([131]))
+        .expect("the empty iterator has length zero")
+}
+
+/// Generates a test vector for `constants`: a preimage derived from `seed`, and its digest. The preimage consists of
+/// the field elements `seed, seed + 1, ...`, as many as the hash type takes: the length of a
+/// [`HashType::ConstantLength`] hash type, and [`Arity`] for all others. Constant-length preimages are hashed with
+/// [`hash_exact`], others with [`Poseidon::hash`].
+///
+/// Vectors are thus reproducible from the constants and the seed alone. The test vectors of this crate's own tests are
+/// those of seed 0.
+pub fn generate_vector<F, A>(constants: &PoseidonConstants<F, A>, seed: u64) -> (Vec<F>, F)
+where
+    F: PrimeField,
+    A: Arity<F>,
+{
+    let length = match constants.hash_type {
+        HashType::ConstantLength(length) => length,
+        _ => A::to_usize(),
+    };
+    let preimage: Vec<F> = (0..length as u64)
+        .map(|i| F::from(seed) + F::from(i))
+        .collect();
+
+    let digest = match constants.hash_type {
+        HashType::ConstantLength(_) => hash_exact(constants, preimage.iter().copied())
+            .expect("the preimage has the length of the iterator"),
+        _ => Poseidon::new_with_preimage(&preimage, constants).hash(),
+    };
+    (preimage, digest)
 }
 
 #[derive(Debug)]
@@ -907,6 +2149,22 @@ where
             .collect())
     }
 
+    /// Writes each digest directly to its slot, without collecting the digests first.
+    fn hash_into_slice(
+        &mut self,
+        target_slice: &mut [F],
+        preimages: &[GenericArray<F, A>],
+    ) -> Result<(), Error> {
+        assert_eq!(target_slice.len(), preimages.len());
+
+        let mut p = Poseidon::new(&self.constants);
+        for (out, preimage) in target_slice.iter_mut().zip(preimages) {
+            p.set_preimage(preimage);
+            p.hash_into(out);
+        }
+        Ok(())
+    }
+
     fn max_batch_size(&self) -> usize {
         self.max_batch_size
     }
@@ -915,12 +2173,15 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::hash_type::CType;
     use crate::sponge::vanilla::SpongeTrait;
     use crate::*;
     use blstrs::Scalar as Fr;
     use ff::Field;
     use generic_array::typenum;
     use pasta_curves::pallas::Scalar as S1;
+    use rand::{Rng, SeedableRng};
+    use rand_xorshift::XorShiftRng;
 
     #[test]
     fn reset() {
@@ -937,6 +2198,134 @@ mod tests {
         assert_eq!(default.constants_offset, h.constants_offset);
     }
 
+    #[test]
+    fn set_preimage_slice() {
+        let constants = PoseidonConstants::<Fr, U4>::new();
+        let mut h = Poseidon::<Fr, U4>::new(&constants);
+
+        let too_short = vec![<Fr as Field>::ONE; 3];
+        match h.set_preimage_slice(&too_short) {
+            Err(Error::InvalidPreimageLength { got, expected }) => {
+                assert_eq!(got, 3);
+                assert_eq!(expected, 4);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let too_long = vec![<Fr as Field>::ONE; 5];
+        match h.set_preimage_slice(&too_long) {
+            Err(Error::InvalidPreimageLength { got, expected }) => {
+                assert_eq!(got, 5);
+                assert_eq!(expected, 4);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        // A failed call leaves the state untouched.
+        assert_eq!(Poseidon::<Fr, U4>::new(&constants), h);
+
+        let preimage = vec![<Fr as Field>::ONE; 4];
+        h.set_preimage_slice(&preimage).unwrap();
+        let mut expected = Poseidon::<Fr, U4>::new_with_preimage(&preimage, &constants);
+        assert_eq!(expected.hash(), h.hash());
+    }
+
+    #[test]
+    fn hash_exact_matches_slice_api() {
+        let constants = PoseidonConstants::<Fr, U4>::new();
+
+        for length in 0..=9 {
+            let preimage = (0..length as u64).map(Fr::from).collect::<Vec<_>>();
+            let digest = hash_exact(&constants, preimage.iter().copied()).unwrap();
+
+            let constant_constants = PoseidonConstants::<Fr, U4>::new_constant_length(length);
+            let expected = if length <= constants.arity() {
+                Poseidon::new_with_preimage(&preimage, &constant_constants).hash()
+            } else {
+                let mut sponge = Sponge::new_with_constants(&constant_constants, Mode::Simplex);
+                sponge.absorb_elements(&preimage, &mut ()).unwrap();
+                sponge.squeeze(&mut ()).unwrap().unwrap()
+            };
+
+            assert_eq!(expected, digest, "mismatch for length {}", length);
+        }
+    }
+
+    #[test]
+    fn hash_exact_rejects_wrong_length() {
+        // An iterator which under-reports its length.
+        struct Lying(std::ops::Range<usize>);
+        impl Iterator for Lying {
+            type Item = Fr;
+            fn next(&mut self) -> Option<Fr> {
+                self.0.next().map(|i| Fr::from(i as u64))
+            }
+        }
+        impl ExactSizeIterator for Lying {
+            fn len(&self) -> usize {
+                self.0.len() - 1
+            }
+        }
+
+        let constants = PoseidonConstants::<Fr, U4>::new();
+        for length in [3, 4, 7] {
+            match hash_exact(&constants, Lying(0..length)) {
+                Err(Error::InvalidPreimageLength { got, expected }) => {
+                    assert_eq!(got, length);
+                    assert_eq!(expected, length - 1);
+                }
+                other => panic!("unexpected result: {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn sanity_check_round_constants() {
+        let constants = PoseidonConstants::<Fr, U4>::new();
+        constants.sanity_check_round_constants().unwrap();
+
+        let mut zeroed = constants.clone();
+        zeroed.round_constants = zeroed.round_constants.map(|rc| vec![Fr::ZERO; rc.len()]);
+        assert!(matches!(
+            zeroed.sanity_check_round_constants(),
+            Err(Error::InvalidRoundConstants(_))
+        ));
+
+        // A single zero is tolerated, but a second one is not.
+        let mut zeros = constants.clone();
+        zeros.compressed_round_constants[3] = Fr::ZERO;
+        zeros.sanity_check_round_constants().unwrap();
+        zeros.compressed_round_constants[10] = Fr::ZERO;
+        assert!(zeros.sanity_check_round_constants().is_err());
+
+        let mut repeated = constants.clone();
+        repeated.round_constants = None;
+        let value = repeated.compressed_round_constants[5];
+        repeated.compressed_round_constants[6] = value;
+        repeated.sanity_check_round_constants().unwrap();
+        repeated.compressed_round_constants[7] = value;
+        assert!(repeated.sanity_check_round_constants().is_err());
+    }
+
+    #[test]
+    fn display_is_compact() {
+        let constants = PoseidonConstants::<Fr, U4>::new();
+        let display = constants.to_string();
+
+        assert!(display.contains("arity: 4"));
+        assert!(display.contains(&format!("full_rounds: {}", constants.full_rounds)));
+        assert!(display.contains(&format!("partial_rounds: {}", constants.partial_rounds)));
+
+        let mds_entry = format!("{:?}", constants.mds_matrices.m[0][0]);
+        assert!(!display.contains(&mds_entry));
+        assert!(format!("{:?}", constants).contains(&mds_entry));
+
+        // The digest distinguishes constants that differ only in their values.
+        let mut other = constants.clone();
+        other.compressed_round_constants[0] += Fr::ONE;
+        assert_ne!(display, other.to_string());
+    }
+
     #[test]
     fn hash_det() {
         let test_arity = 2;
@@ -1124,6 +2513,8 @@ mod tests {
                     }
                 }
             }
+            #[cfg(feature = "insecure-fast")]
+            Strength::Fast => unreachable!("hash_values only covers the standard strengths"),
         };
 
         let mut constant_sponge = Sponge::new_with_constants(&constant_constants, Mode::Simplex);
@@ -1238,15 +2629,130 @@ mod tests {
                 ]),
                 _ => unimplemented!(),
             },
+            #[cfg(feature = "insecure-fast")]
+            Strength::Fast => unreachable!("hash_values only covers the standard strengths"),
         };
         assert_eq!(expected_constant, constant_sponge_digest.unwrap());
 
         assert_eq!(expected, digest);
+
+        // The vectors are those generated with seed 0.
+        let (preimage, vector_digest) = generate_vector(&merkle_constants, 0);
+        assert_eq!(
+            (0..test_arity as u64).map(Fr::from).collect::<Vec<_>>(),
+            preimage
+        );
+        assert_eq!(expected, vector_digest);
+        let (preimage, vector_digest) = generate_vector(&constant_constants, 0);
+        assert_eq!(constant_length, preimage.len());
+        assert_eq!(expected_constant, vector_digest);
+        assert_ne!(
+            (preimage, vector_digest),
+            generate_vector(&constant_constants, 1)
+        );
     }
 
+    /// Golden digests of two inputs under the default constants, with any remaining preimage elements zero. Any
+    /// change to the MDS matrices, round constants or round schedule changes these.
     #[test]
-    fn hash_compare_optimized() {
-        let constants = PoseidonConstants::<Fr, U2>::new();
+    fn golden_two_input_compressions() {
+        fn compress_two<A: Arity<Fr>>(left: Fr, right: Fr) -> Fr {
+            let constants = PoseidonConstants::<Fr, A>::new();
+            let mut p = Poseidon::<Fr, A>::new(&constants);
+            p.input(left).unwrap();
+            p.input(right).unwrap();
+            p.hash()
+        }
+
+        let golden: [(usize, u64, u64, [u64; 4]); 6] = [
+            (
+                2,
+                0,
+                0,
+                [
+                    0x8be5147f0e4613d4,
+                    0x6fd388e1ef1d3d41,
+                    0xdb33a7c6e5af61b7,
+                    0x48fe0b1331196f6c,
+                ],
+            ),
+            (
+                2,
+                1,
+                2,
+                [
+                    0x9317d9833e5fa9be,
+                    0x69c04e726e5896d8,
+                    0x7babcbaf436a9d76,
+                    0x6d6f8106657f1f4d,
+                ],
+            ),
+            (
+                4,
+                0,
+                0,
+                [
+                    0x64472e952b9711bc,
+                    0xf99a58932383621b,
+                    0x0f13b878b866d3ee,
+                    0x65cec475d81e7e5f,
+                ],
+            ),
+            (
+                4,
+                1,
+                2,
+                [
+                    0xf3183f77c1439a3d,
+                    0x5b5b83adc882ded0,
+                    0x67a257514e6c9eb5,
+                    0x56a5b25b0822b16a,
+                ],
+            ),
+            (
+                8,
+                0,
+                0,
+                [
+                    0x3482b431caff9b3a,
+                    0x337c0be60db389d1,
+                    0x04a036088bc029f5,
+                    0x3f79e614b63889f9,
+                ],
+            ),
+            (
+                8,
+                1,
+                2,
+                [
+                    0x2816e3f93806201c,
+                    0x279acd9c24130a24,
+                    0xf71207391831a621,
+                    0x3a16d2e89bd08ab5,
+                ],
+            ),
+        ];
+
+        for (arity, left, right, expected) in golden {
+            let (left, right) = (Fr::from(left), Fr::from(right));
+            let digest = match arity {
+                2 => compress_two::<U2>(left, right),
+                4 => compress_two::<U4>(left, right),
+                8 => compress_two::<U8>(left, right),
+                _ => unreachable!(),
+            };
+            assert_eq!(
+                scalar_from_u64s(expected),
+                digest,
+                "golden digest changed for arity {}",
+                arity
+            );
+        }
+    }
+
+    #[test]
+    fn hash_compare_optimized() {
+        let constants = PoseidonConstants::<Fr, U2>::new();
         let mut p = Poseidon::<Fr, U2>::new(&constants);
         let test_arity = constants.arity();
         for n in 0..test_arity {
@@ -1265,6 +2771,1069 @@ mod tests {
         assert_eq!(digest_correct, digest_optimized_static);
     }
 
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Round schedule requires")]
+    fn constants_offset_detects_short_round_constants() {
+        let mut constants = PoseidonConstants::<Fr, U2>::new();
+        let short_len = (constants.full_rounds + constants.partial_rounds) * constants.width() - 1;
+        constants
+            .round_constants
+            .as_mut()
+            .unwrap()
+            .truncate(short_len);
+
+        let mut p = Poseidon::<Fr, U2>::new_with_preimage(&[Fr::ONE, Fr::ONE], &constants);
+        p.hash_in_mode(Correct);
+    }
+
+    #[test]
+    fn dense_export_roundtrip() {
+        let constants = PoseidonConstants::<Fr, U4>::new();
+        let preimage: Vec<Fr> = (0..4u64).map(Fr::from).collect();
+        let import = |constants: &PoseidonConstants<Fr, U4>| {
+            PoseidonConstants::<Fr, U4>::from_parts(
+                constants.mds_dense(),
+                constants.round_constants_dense().unwrap(),
+                constants.full_rounds,
+                constants.partial_rounds,
+                constants.strength,
+                constants.hash_type.clone(),
+            )
+        };
+
+        assert_eq!(constants, import(&constants));
+
+        // Deserialized constants hold only the compressed round constants, which are decompressed.
+        let mut compressed_only = constants.clone();
+        compressed_only.round_constants = None;
+        let imported = import(&compressed_only);
+        assert_eq!(constants, imported);
+        assert_eq!(
+            Poseidon::new_with_preimage(&preimage, &constants).hash(),
+            Poseidon::new_with_preimage(&preimage, &imported).hash()
+        );
+
+        // Custom round constants cannot be recovered from their compressed form.
+        let mut custom_round_constants = constants.round_constants.clone().unwrap();
+        custom_round_constants[0] += Fr::ONE;
+        let mut custom = PoseidonConstants::<Fr, U4>::from_parts(
+            constants.mds_dense(),
+            custom_round_constants,
+            constants.full_rounds,
+            constants.partial_rounds,
+            constants.strength,
+            constants.hash_type.clone(),
+        );
+        assert!(custom.round_constants_dense().is_ok());
+        custom.round_constants = None;
+        assert!(custom.round_constants_dense().is_err());
+    }
+
+    #[cfg(feature = "insecure-fast")]
+    #[test]
+    fn fast_is_separated_from_standard() {
+        let standard = PoseidonConstants::<Fr, U4>::new_with_strength(Strength::Standard);
+        let fast = PoseidonConstants::<Fr, U4>::new_with_strength(Strength::Fast);
+        assert!(fast.partial_rounds < standard.partial_rounds);
+        assert_ne!(standard.domain_tag, fast.domain_tag);
+
+        let preimage: Vec<Fr> = (0..4u64).map(Fr::from).collect();
+        let mut p = Poseidon::new_with_preimage(&preimage, &fast);
+        let mut p2 = p.clone();
+        let digest = p.hash_in_mode(Correct);
+        assert_eq!(digest, p2.hash_in_mode(OptimizedStatic));
+        assert_ne!(
+            Poseidon::new_with_preimage(&preimage, &standard).hash(),
+            digest
+        );
+
+        let constant_length = PoseidonConstants::<Fr, U4>::new_with_strength_and_type(
+            Strength::Fast,
+            HashType::ConstantLength(4),
+        );
+        assert_ne!(
+            hash_exact(&standard, preimage.iter().copied()).unwrap(),
+            hash_exact(&fast, preimage.iter().copied()).unwrap()
+        );
+        assert_eq!(
+            constant_length.domain_tag,
+            HashType::<Fr, U4>::ConstantLength(4).domain_tag_with_strength(Strength::Fast)
+        );
+    }
+
+    #[test]
+    fn optimized_matches_correct_for_random_constants() {
+        let mut rng = XorShiftRng::from_seed(crate::TEST_SEED);
+        for _ in 0..4 {
+            random_constants_aux::<U2>(&mut rng);
+            random_constants_aux::<U4>(&mut rng);
+            random_constants_aux::<U8>(&mut rng);
+        }
+    }
+
+    /// Builds constants from a random symmetric Cauchy MDS matrix (the optimized modes rely on symmetry, as the
+    /// generated matrices have it), random round constants and random round numbers, and checks that all hash modes
+    /// agree on a random preimage.
+    fn random_constants_aux<A: Arity<Fr>>(rng: &mut XorShiftRng) {
+        let width = A::ConstantsSize::to_usize();
+        let xs: Vec<Fr> = (0..width).map(|_| Fr::random(&mut *rng)).collect();
+        let mds: Matrix<Fr> = xs
+            .iter()
+            .map(|x| xs.iter().map(|y| (*x + y).invert().unwrap()).collect())
+            .collect();
+
+        let full_rounds = 2 * rng.gen_range(1..=4);
+        let partial_rounds = rng.gen_range(1..=60);
+        let round_constants = (0..width * (full_rounds + partial_rounds))
+            .map(|_| Fr::random(&mut *rng))
+            .collect();
+
+        let constants = PoseidonConstants::<Fr, A>::from_parts(
+            mds,
+            round_constants,
+            full_rounds,
+            partial_rounds,
+            Strength::Standard,
+            HashType::MerkleTree,
+        );
+
+        let preimage: Vec<Fr> = (0..A::to_usize()).map(|_| Fr::random(&mut *rng)).collect();
+        let mut p = Poseidon::<Fr, A>::new_with_preimage(&preimage, &constants);
+        let mut p2 = p.clone();
+        let mut p3 = p.clone();
+
+        let digest = p.hash_in_mode(Correct);
+        assert_eq!(
+            digest,
+            p2.hash_in_mode(OptimizedDynamic),
+            "width {}, rounds ({}, {})",
+            width,
+            full_rounds,
+            partial_rounds
+        );
+        assert_eq!(
+            digest,
+            p3.hash_in_mode(OptimizedStatic),
+            "width {}, rounds ({}, {})",
+            width,
+            full_rounds,
+            partial_rounds
+        );
+    }
+
+    #[test]
+    fn arity_one() {
+        let constants = PoseidonConstants::<Fr, U1>::new();
+        assert_eq!(2, constants.width());
+        assert_eq!(Fr::ONE, constants.domain_tag);
+        assert_eq!((8, 55), (constants.full_rounds, constants.partial_rounds));
+
+        // Values cross-checked against an independent implementation of the reference permutation.
+        for (input, expected) in [
+            (
+                0,
+                scalar_from_u64s([
+                    0x5c40b864e73bd7df,
+                    0x763876493833415e,
+                    0xf5052eecd7ae62fd,
+                    0x23604186a361a3cb,
+                ]),
+            ),
+            (
+                1,
+                scalar_from_u64s([
+                    0x1a135bc736da184c,
+                    0x315ba091e49ffe9d,
+                    0x56bcd8f59bad437d,
+                    0x3be53651b8492fc0,
+                ]),
+            ),
+        ] {
+            let mut p = Poseidon::<Fr, U1>::new_with_preimage(&[Fr::from(input)], &constants);
+            let mut p2 = p.clone();
+            let mut p3 = p.clone();
+            assert_eq!(expected, p.hash_in_mode(Correct));
+            assert_eq!(expected, p2.hash_in_mode(OptimizedDynamic));
+            assert_eq!(expected, p3.hash_in_mode(OptimizedStatic));
+        }
+    }
+
+    #[test]
+    fn hash_digests_below_and_above_arity() {
+        let constants = PoseidonConstants::<Fr, U4>::new();
+        let digests: Vec<Fr> = (0..21u64).map(Fr::from).collect();
+        let h = |group: &[Fr]| hash_exact(&constants, group.iter().copied()).unwrap();
+
+        assert!(hash_digests(&constants, &[]).is_err());
+        for n in 1..=4 {
+            assert_eq!(
+                h(&digests[..n]),
+                hash_digests(&constants, &digests[..n]).unwrap()
+            );
+        }
+
+        // Five digests: one full group and a single leftover.
+        assert_eq!(
+            h(&[h(&digests[..4]), h(&digests[4..5])]),
+            hash_digests(&constants, &digests[..5]).unwrap()
+        );
+
+        // 21 digests: six nodes, then two, then the root.
+        let row: Vec<Fr> = digests.chunks(4).map(h).collect();
+        let row: Vec<Fr> = row.chunks(4).map(h).collect();
+        assert_eq!(h(&row), hash_digests(&constants, &digests).unwrap());
+        assert_ne!(
+            hash_digests(&constants, &digests[..20]).unwrap(),
+            hash_digests(&constants, &digests).unwrap()
+        );
+    }
+
+    #[test]
+    fn all_zero_preimages() {
+        fn check<A: Arity<Fr>>(expected: Fr) {
+            let constants = PoseidonConstants::<Fr, A>::new();
+            let preimage = vec![Fr::ZERO; A::to_usize()];
+            let mut p = Poseidon::<Fr, A>::new_with_preimage(&preimage, &constants);
+            let mut p2 = p.clone();
+
+            let digest = p.hash();
+            assert_ne!(Fr::ZERO, digest);
+            assert_eq!(expected, digest, "arity {}", A::to_usize());
+            assert_eq!(expected, p2.hash_in_mode(Correct));
+        }
+
+        // Values cross-checked against an independent implementation of the reference permutation.
+        check::<U1>(scalar_from_u64s([
+            0x5c40b864e73bd7df,
+            0x763876493833415e,
+            0xf5052eecd7ae62fd,
+            0x23604186a361a3cb,
+        ]));
+        check::<U2>(scalar_from_u64s([
+            0x8be5147f0e4613d4,
+            0x6fd388e1ef1d3d41,
+            0xdb33a7c6e5af61b7,
+            0x48fe0b1331196f6c,
+        ]));
+        check::<U4>(scalar_from_u64s([
+            0x64472e952b9711bc,
+            0xf99a58932383621b,
+            0x0f13b878b866d3ee,
+            0x65cec475d81e7e5f,
+        ]));
+        check::<U8>(scalar_from_u64s([
+            0x3482b431caff9b3a,
+            0x337c0be60db389d1,
+            0x04a036088bc029f5,
+            0x3f79e614b63889f9,
+        ]));
+    }
+
+    #[test]
+    fn composed_rounds_match_hash() {
+        let constants = PoseidonConstants::<Fr, U4>::new();
+        let preimage: Vec<Fr> = (0..4u64).map(Fr::from).collect();
+        let expected = Poseidon::new_with_preimage(&preimage, &constants).hash();
+
+        let mut p = Poseidon::new_with_preimage(&preimage, &constants);
+        p.set_constants_offset(0).unwrap();
+        p.run_full_rounds(constants.half_full_rounds).unwrap();
+        p.run_partial_rounds(constants.partial_rounds).unwrap();
+        p.run_full_rounds(constants.half_full_rounds).unwrap();
+        assert_eq!(expected, p.elements[1]);
+
+        // All round constants have been consumed.
+        let len = constants.round_constants.as_ref().unwrap().len();
+        assert_eq!(len, p.constants_offset());
+        assert!(matches!(
+            p.run_partial_rounds(1),
+            Err(Error::InsufficientRoundConstants { .. })
+        ));
+        assert!(matches!(
+            p.set_constants_offset(len + 1),
+            Err(Error::IndexOutOfBounds)
+        ));
+
+        let mut compressed_only = constants.clone();
+        compressed_only.round_constants = None;
+        let mut p = Poseidon::new_with_preimage(&preimage, &compressed_only);
+        assert!(p.run_full_rounds(1).is_err());
+    }
+
+    #[test]
+    fn domain_tag_matches_hash_type() {
+        for hash_type in [
+            HashType::MerkleTree,
+            HashType::ConstantLength(3),
+            HashType::Encryption,
+            HashType::Custom(CType::Arbitrary(7)),
+            HashType::Sponge,
+        ] {
+            for strength in [Strength::Standard, Strength::Strengthened] {
+                let constants = PoseidonConstants::<Fr, U4>::new_with_strength_and_type(
+                    strength,
+                    hash_type.clone(),
+                );
+                assert_eq!(hash_type.domain_tag(), constants.domain_tag());
+            }
+        }
+        assert_eq!(
+            Fr::from(15),
+            PoseidonConstants::<Fr, U4>::new().domain_tag()
+        );
+    }
+
+    #[test]
+    fn arity_tag_does_not_overflow() {
+        assert_eq!(Fr::from(3), <U2 as Arity<Fr>>::tag());
+        assert_eq!(Fr::from((1 << 36) - 1), <U36 as Arity<Fr>>::tag());
+
+        assert_eq!(Some(0), arity_tag_u64(0));
+        assert_eq!(Some((1 << 63) - 1), arity_tag_u64(63));
+        assert_eq!(Some(u64::MAX), arity_tag_u64(64));
+        assert_eq!(None, arity_tag_u64(65));
+        assert_eq!(None, arity_tag_u64(usize::MAX));
+
+        for arity in [0, 1, 36, 63, 64] {
+            assert_eq!(
+                Fr::from(arity_tag_u64(arity).unwrap()),
+                arity_tag::<Fr>(arity)
+            );
+        }
+        assert_eq!(Fr::from(u64::MAX).double() + Fr::ONE, arity_tag::<Fr>(65));
+    }
+
+    #[cfg(feature = "nonstandard-interop")]
+    #[test]
+    fn no_tag_vector() {
+        let constants = PoseidonConstants::<Fr, U2>::new();
+        let preimage = [Fr::from(1), Fr::from(2)];
+
+        // Computed with an independent implementation of the reference permutation, on the state [0, 1, 2].
+        let expected = scalar_from_u64s([
+            0x409ad8e7db8d8e9b,
+            0x43d253af0207f417,
+            0xc68235834c3a46f8,
+            0x0106f5f58a687d47,
+        ]);
+        assert_eq!(expected, hash_no_tag(&constants, &preimage));
+        assert_ne!(
+            Poseidon::new_with_preimage(&preimage, &constants).hash(),
+            expected
+        );
+    }
+
+    #[test]
+    fn hash_in_place_matches_hash() {
+        let constants = PoseidonConstants::<Fr, U4>::new();
+        let preimage: Vec<Fr> = (1..=4u64).map(Fr::from).collect();
+
+        let mut state = GenericArray::<Fr, U5>::generate(|i| {
+            if i == 0 {
+                constants.domain_tag()
+            } else {
+                preimage[i - 1]
+            }
+        });
+        let digest = hash_in_place(&constants, &mut state);
+
+        let mut p = Poseidon::new_with_preimage(&preimage, &constants);
+        assert_eq!(p.hash(), digest);
+        assert_eq!(p.elements, state);
+    }
+
+    #[test]
+    fn hash_wide() {
+        let constants = PoseidonConstants::<Fr, U2>::new();
+        let preimage = [Fr::from(1), Fr::from(2)];
+
+        let digest = Poseidon::new_with_preimage(&preimage, &constants).hash();
+        assert_eq!(
+            vec![digest],
+            Poseidon::new_with_preimage(&preimage, &constants).hash_wide(1)
+        );
+
+        let wide = Poseidon::new_with_preimage(&preimage, &constants).hash_wide(2);
+        assert_eq!(2, wide.len());
+        assert_eq!(digest, wide[0]);
+        assert_ne!(wide[0], wide[1]);
+    }
+
+    #[test]
+    fn new_validated() {
+        assert!(PoseidonConstants::<Fr, U2>::new_validated().is_ok());
+        assert!(PoseidonConstants::<Fr, U8>::new_validated().is_ok());
+        assert!(PoseidonConstants::<S1, U4>::new_validated().is_ok());
+
+        let constants = PoseidonConstants::<Fr, U2>::new();
+        let parts = |round_constants: Vec<Fr>| {
+            PoseidonConstants::<Fr, U2>::from_parts(
+                constants.mds_matrices.m.clone(),
+                round_constants,
+                constants.full_rounds,
+                constants.partial_rounds,
+                Strength::Standard,
+                HashType::MerkleTree,
+            )
+        };
+        let round_constants = constants.round_constants.clone().unwrap();
+        assert!(parts(round_constants.clone()).validate().is_ok());
+
+        let mut zeroed = round_constants.clone();
+        zeroed[..20].iter_mut().for_each(|c| *c = Fr::ZERO);
+        assert!(matches!(
+            parts(zeroed).validate(),
+            Err(Error::InvalidRoundConstants(_))
+        ));
+
+        let mut corrupted = parts(round_constants.clone());
+        corrupted.sparse_matrixes[0].w_hat[1] += Fr::ONE;
+        assert!(matches!(corrupted.validate(), Err(Error::Other(_))));
+
+        let mut corrupted = parts(round_constants);
+        corrupted.compressed_round_constants[5] += Fr::ONE;
+        assert!(matches!(
+            corrupted.validate(),
+            Err(Error::InvalidRoundConstants(_))
+        ));
+    }
+
+    #[cfg(feature = "secp256k1")]
+    #[test]
+    fn secp256k1() {
+        use k256::Scalar as K;
+
+        // Big-endian hex, as the `Repr` of `k256::Scalar` is big-endian.
+        let from_hex = |hex: &str| {
+            let mut repr = <K as PrimeField>::Repr::default();
+            for (i, byte) in repr.iter_mut().enumerate() {
+                *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap();
+            }
+            K::from_repr(repr).unwrap()
+        };
+
+        let constants = PoseidonConstants::<K, U2>::new_validated().unwrap();
+        assert_eq!((8, 55), (constants.full_rounds, constants.partial_rounds));
+        assert_eq!(
+            from_hex("b9d410791659fcc1401750ee27e728733309cfcfcffdcfc2a07785b8aade5319"),
+            constants.round_constants.as_ref().unwrap()[0]
+        );
+
+        let preimage = [K::from(1u64), K::from(2u64)];
+        let digest = Poseidon::new_with_preimage(&preimage, &constants).hash();
+        assert_eq!(
+            from_hex("06454469ebaad9177228d543180be094263453e3ec14f4c8c847e8080293bea8"),
+            digest
+        );
+        assert_eq!(
+            digest,
+            Poseidon::new_with_preimage(&preimage, &PoseidonConstants::<K, U2>::new()).hash()
+        );
+        assert_eq!(
+            digest,
+            Poseidon::new_with_preimage(&preimage, &constants).hash_in_mode(Correct)
+        );
+    }
+
+    #[test]
+    fn hash_auto_matches_hash() {
+        fn check<A: Arity<Fr>>() {
+            let constants = PoseidonConstants::<Fr, A>::new();
+            assert_eq!(OptimizedStatic, constants.preferred_hash_mode());
+
+            let preimage: Vec<Fr> = (0..A::to_usize() as u64).map(Fr::from).collect();
+            let mut p = Poseidon::new_with_preimage(&preimage, &constants);
+            let digest = p.hash();
+            p.set_preimage(&preimage);
+            assert_eq!(digest, p.hash_auto());
+        }
+
+        check::<U2>();
+        check::<U11>();
+
+        // Without uncompressed round constants, only the optimized mode is possible.
+        let mut constants = PoseidonConstants::<Fr, U2>::new();
+        constants.round_constants = None;
+        assert_eq!(OptimizedStatic, constants.preferred_hash_mode());
+    }
+
+    #[test]
+    fn save_and_load_state() {
+        let constants = PoseidonConstants::<Fr, U4>::new_constant_length(3);
+        let preimage = [Fr::from(1), Fr::from(2), Fr::from(3)];
+        let digest = Poseidon::new_with_preimage(&preimage, &constants).hash();
+
+        let mut p = Poseidon::new(&constants);
+        p.input(preimage[0]).unwrap();
+        p.input(preimage[1]).unwrap();
+        let saved = bincode::serialize(&p.save_state()).unwrap();
+
+        let state: PoseidonState<Fr> = bincode::deserialize(&saved).unwrap();
+        let mut restored = Poseidon::new(&constants);
+        restored.load_state(&state).unwrap();
+        restored.input(preimage[2]).unwrap();
+        assert_eq!(digest, restored.hash());
+
+        let mut wrong_width = state.clone();
+        wrong_width.elements.pop();
+        assert!(restored.load_state(&wrong_width).is_err());
+        let mut wrong_pos = state;
+        wrong_pos.pos = 6;
+        assert!(restored.load_state(&wrong_pos).is_err());
+    }
+
+    #[test]
+    fn hash_salted() {
+        let constants = PoseidonConstants::<Fr, U2>::new();
+        let preimage = [Fr::from(1), Fr::from(2)];
+        let digest = Poseidon::new_with_preimage(&preimage, &constants).hash();
+
+        assert_eq!(digest, super::hash_salted(&constants, Fr::ZERO, &preimage));
+
+        let salted1 = super::hash_salted(&constants, Fr::from(11), &preimage);
+        let salted2 = super::hash_salted(&constants, Fr::from(12), &preimage);
+        assert_ne!(digest, salted1);
+        assert_ne!(salted1, salted2);
+        assert_eq!(
+            salted1,
+            super::hash_salted(&constants, Fr::from(11), &preimage)
+        );
+    }
+
+    #[test]
+    fn max_arity() {
+        assert_eq!(MAX_ARITY, super::max_arity::<Fr>());
+        assert_eq!(MAX_ARITY, super::max_arity::<S1>());
+        assert_eq!(MAX_ARITY, U36::to_usize());
+    }
+
+    #[test]
+    fn try_new_reports_mds_generation_failure() {
+        // The Cauchy matrix of width `t` has denominators `x + y` for `x < t <= y < 2t`, one of which is zero in a
+        // field of order less than `2t - 1`.
+        #[derive(ff::PrimeField)]
+        #[PrimeFieldModulus = "7"]
+        #[PrimeFieldGenerator = "3"]
+        #[PrimeFieldReprEndianness = "little"]
+        struct F7([u64; 1]);
+
+        assert!(matches!(
+            PoseidonConstants::<F7, U2>::try_new(),
+            Err(Error::MdsGenerationFailed(_))
+        ));
+        // Arities whose domain tags are not distinct in the field are rejected before generating the MDS matrix.
+        assert_eq!(2, super::max_arity::<F7>());
+        assert!(matches!(
+            PoseidonConstants::<F7, U4>::try_new(),
+            Err(Error::ArityTooLarge { max: 2 })
+        ));
+
+        assert_eq!(
+            PoseidonConstants::<Fr, U4>::new(),
+            PoseidonConstants::<Fr, U4>::try_new().unwrap()
+        );
+        assert_eq!(
+            PoseidonConstants::<Fr, U2>::new_constant_length(1),
+            PoseidonConstants::<Fr, U2>::try_new_with_strength_and_type(
+                Strength::Standard,
+                HashType::ConstantLength(1)
+            )
+            .unwrap()
+        );
+        assert!(PoseidonConstants::<Fr, U2>::try_new_with_strength_and_type(
+            Strength::Standard,
+            HashType::VariableLength
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn new_with_domain_tag() {
+        let preimage = [Fr::from(1), Fr::from(2)];
+        let hash = |constants: &PoseidonConstants<Fr, U2>| {
+            Poseidon::new_with_preimage(&preimage, constants).hash()
+        };
+
+        let a = PoseidonConstants::<Fr, U2>::new_with_domain_tag(Fr::from(1234));
+        let b = PoseidonConstants::<Fr, U2>::new_with_domain_tag(Fr::from(5678));
+        assert_eq!(Fr::from(1234), a.domain_tag());
+        assert_ne!(hash(&a), hash(&b));
+
+        let standard = PoseidonConstants::<Fr, U2>::new();
+        assert_ne!(hash(&standard), hash(&a));
+        let same_tag = PoseidonConstants::<Fr, U2>::new_with_domain_tag(standard.domain_tag());
+        assert_eq!(hash(&standard), hash(&same_tag));
+    }
+
+    #[test]
+    fn zero_partial_rounds() {
+        let standard = PoseidonConstants::<Fr, U2>::new();
+        let width = standard.width();
+        let full_only = PoseidonConstants::<Fr, U2>::from_parts(
+            standard.mds_matrices.m.clone(),
+            standard.round_constants.clone().unwrap()[..width * standard.full_rounds].to_vec(),
+            standard.full_rounds,
+            0,
+            Strength::Standard,
+            HashType::MerkleTree,
+        );
+        assert!(full_only.sparse_matrixes.is_empty());
+
+        let preimage = [Fr::from(1), Fr::from(2)];
+        let hash = |mode| Poseidon::new_with_preimage(&preimage, &full_only).hash_in_mode(mode);
+        let digest = hash(HashMode::OptimizedStatic);
+        assert_eq!(digest, hash(HashMode::Correct));
+        assert_eq!(digest, hash(HashMode::OptimizedDynamic));
+        assert_ne!(
+            digest,
+            Poseidon::new_with_preimage(&preimage, &standard).hash()
+        );
+    }
+
+    #[test]
+    fn fingerprint() {
+        let constants = PoseidonConstants::<Fr, U4>::new();
+        let mut without_round_constants = constants.clone();
+        without_round_constants.round_constants = None;
+        assert_eq!(
+            constants.fingerprint(),
+            without_round_constants.fingerprint()
+        );
+        assert_eq!(
+            constants.fingerprint(),
+            PoseidonConstants::<Fr, U4>::new().fingerprint()
+        );
+
+        assert_ne!(
+            constants.fingerprint(),
+            PoseidonConstants::<Fr, U4>::new_with_strength(Strength::Strengthened).fingerprint()
+        );
+        assert_ne!(
+            constants.fingerprint(),
+            PoseidonConstants::<Fr, U4>::new_with_domain_tag(Fr::from(7)).fingerprint()
+        );
+        assert_ne!(
+            constants.fingerprint(),
+            PoseidonConstants::<S1, U4>::new().fingerprint()
+        );
+    }
+
+    #[test]
+    fn field_modulus_bits() {
+        assert_eq!(255, PoseidonConstants::<Fr, U2>::new().field_modulus_bits());
+        assert_eq!(255, PoseidonConstants::<S1, U2>::new().field_modulus_bits());
+    }
+
+    #[test]
+    fn estimate_constraints() {
+        let constants = PoseidonConstants::<Fr, U2>::new();
+        assert_eq!((8, 55), (constants.full_rounds, constants.partial_rounds));
+        assert_eq!(3 * (3 * 8 + 55), constants.estimate_constraints());
+    }
+
+    #[test]
+    fn hash_with_scratch() {
+        let constants = PoseidonConstants::<Fr, U4>::new();
+        let mut scratch: GenericArray<Fr, U5> = GenericArray::generate(|i| Fr::from(i as u64 + 7));
+        let mut p = Poseidon::new(&constants);
+
+        for i in 0..4u64 {
+            let preimage: Vec<Fr> = (0..4).map(|j| Fr::from(i * 4 + j)).collect();
+            let expected = Poseidon::new_with_preimage(&preimage, &constants).hash();
+
+            p.set_preimage(&preimage);
+            assert_eq!(expected, p.hash_with_scratch(&mut scratch));
+        }
+    }
+
+    #[test]
+    fn hash_matrix() {
+        let constants = PoseidonConstants::<Fr, U4>::new();
+        let matrix = |rows: usize, columns: usize| -> Vec<Vec<Fr>> {
+            (0..rows)
+                .map(|i| {
+                    (0..columns)
+                        .map(|j| Fr::from((i * columns + j) as u64))
+                        .collect()
+                })
+                .collect()
+        };
+
+        // Rows both shorter and longer than the arity, and more rows than the arity.
+        for (rows, columns) in [(3, 2), (2, 3), (6, 5)] {
+            let m = matrix(rows, columns);
+            let commitment = super::hash_matrix(&constants, &m).unwrap();
+
+            let mut swapped = m.clone();
+            swapped.swap(0, 1);
+            assert_ne!(
+                commitment,
+                super::hash_matrix(&constants, &swapped).unwrap()
+            );
+
+            let mut changed = m.clone();
+            changed[rows - 1][columns - 1] += Fr::ONE;
+            assert_ne!(
+                commitment,
+                super::hash_matrix(&constants, &changed).unwrap()
+            );
+
+            // The two levels are domain-separated.
+            let digests: Vec<Fr> = m
+                .iter()
+                .map(|row| hash_exact(&constants, row.iter().copied()).unwrap())
+                .collect();
+            assert_ne!(
+                commitment,
+                hash_exact(&constants, digests.into_iter()).unwrap()
+            );
+        }
+        assert_ne!(
+            super::hash_matrix(&constants, &matrix(3, 2)).unwrap(),
+            super::hash_matrix(&constants, &matrix(2, 3)).unwrap()
+        );
+
+        let mut ragged = matrix(3, 2);
+        ragged[1].pop();
+        assert!(super::hash_matrix(&constants, &ragged).is_err());
+        assert!(super::hash_matrix(&constants, &[]).is_err());
+        assert!(super::hash_matrix(&constants, &[vec![], vec![]]).is_err());
+    }
+
+    #[cfg(feature = "trace")]
+    #[test]
+    fn hash_trace() {
+        let constants = PoseidonConstants::<Fr, U4>::new();
+        let preimage = [Fr::from(1), Fr::from(2), Fr::from(3), Fr::from(4)];
+
+        let trace = Poseidon::new_with_preimage(&preimage, &constants)
+            .hash_trace()
+            .unwrap();
+        assert_eq!(
+            constants.full_rounds + constants.partial_rounds,
+            trace.len()
+        );
+        assert_eq!(
+            Poseidon::new_with_preimage(&preimage, &constants).hash(),
+            trace.last().unwrap()[1]
+        );
+    }
+
+    #[test]
+    fn hash_into() {
+        let constants = PoseidonConstants::<Fr, U4>::new();
+        let preimages: Vec<GenericArray<Fr, U4>> = (0..5u64)
+            .map(|i| GenericArray::generate(|j| Fr::from(i * 4 + j as u64)))
+            .collect();
+
+        let mut out = Fr::ZERO;
+        Poseidon::new_with_preimage(&preimages[0], &constants).hash_into(&mut out);
+        assert_eq!(
+            Poseidon::new_with_preimage(&preimages[0], &constants).hash(),
+            out
+        );
+
+        let mut batcher = SimplePoseidonBatchHasher::<Fr, U4>::new(preimages.len());
+        let mut slots = vec![Fr::ZERO; preimages.len()];
+        batcher.hash_into_slice(&mut slots, &preimages).unwrap();
+        assert_eq!(batcher.hash(&preimages).unwrap(), slots);
+    }
+
+    #[test]
+    fn finish() {
+        let constants = PoseidonConstants::<Fr, U4>::new();
+        let mut p = Poseidon::new(&constants);
+        p.input(Fr::from(7)).unwrap();
+        p.input(Fr::from(8)).unwrap();
+
+        assert_eq!(p.clone().hash(), p.finish());
+    }
+
+    #[cfg(feature = "bn254")]
+    #[test]
+    fn bn254() {
+        use halo2curves::bn256::Fr as B;
+
+        // Big-endian hex, reversed into the little-endian `Repr` of `bn256::Fr`.
+        let from_hex = |hex: &str| {
+            let mut repr = <B as PrimeField>::Repr::default();
+            for (i, byte) in repr.as_mut().iter_mut().rev().enumerate() {
+                *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap();
+            }
+            B::from_repr(repr).unwrap()
+        };
+
+        let constants = PoseidonConstants::<B, U2>::new_validated().unwrap();
+        assert_eq!(254, constants.field_modulus_bits());
+        assert_eq!((8, 55), (constants.full_rounds, constants.partial_rounds));
+        assert_eq!(
+            from_hex("1051abd795bb781c5bcb3d4c7320b88f033cb1904c5b8559bf08995be4d6305d"),
+            constants.round_constants.as_ref().unwrap()[0]
+        );
+        assert_eq!(
+            constants.fingerprint(),
+            PoseidonConstants::<B, U2>::new().fingerprint()
+        );
+
+        let preimage = [B::from(1u64), B::from(2u64)];
+        let digest = Poseidon::new_with_preimage(&preimage, &constants).hash();
+        assert_eq!(
+            from_hex("17913732bd28f1e73f4cb7bae1a9949d071ee1ea41784725a47c880c40b9e6fb"),
+            digest
+        );
+        assert_eq!(
+            digest,
+            Poseidon::new_with_preimage(&preimage, &constants).hash_in_mode(Correct)
+        );
+
+        let constants = PoseidonConstants::<B, U4>::new();
+        let preimage = [1u64, 2, 3, 4].map(B::from);
+        assert_eq!(
+            from_hex("13fca66cb5d24899e87959a9b844f169dcad453b59ad4301a40d206fe12b7ff7"),
+            Poseidon::new_with_preimage(&preimage, &constants).hash()
+        );
+    }
+
+    #[test]
+    fn hash_any_length() {
+        let constants = PoseidonConstants::<Fr, U4>::new();
+        let elements: Vec<Fr> = (1..=17u64).map(Fr::from).collect();
+
+        assert!(super::hash_any_length(&constants, &[]).is_err());
+
+        let digests: Vec<Fr> = (1..=elements.len())
+            .map(|length| super::hash_any_length(&constants, &elements[..length]).unwrap())
+            .collect();
+        for (i, digest) in digests.iter().enumerate() {
+            assert_eq!(
+                *digest,
+                super::hash_any_length(&constants, &elements[..=i]).unwrap()
+            );
+            assert!(digests[i + 1..].iter().all(|other| other != digest));
+        }
+
+        // Up to the arity, digests are those of `hash_exact`.
+        assert_eq!(
+            digests[3],
+            hash_exact(&constants, elements[..4].iter().copied()).unwrap()
+        );
+
+        // Beyond it, roots differ from the digest of the nodes below them.
+        for length in [5, 8, 17] {
+            assert_ne!(
+                digests[length - 1],
+                hash_digests(&constants, &elements[..length]).unwrap()
+            );
+        }
+        let nodes: Vec<Fr> = elements[..8]
+            .chunks(4)
+            .map(|group| hash_exact(&constants, group.iter().copied()).unwrap())
+            .collect();
+        assert_ne!(
+            digests[7],
+            super::hash_any_length(&constants, &nodes).unwrap()
+        );
+    }
+
+    #[test]
+    fn supports_optimized() {
+        let constants = PoseidonConstants::<Fr, U4>::new();
+        assert!(constants.supports_optimized());
+        let preimage = [Fr::from(1), Fr::from(2), Fr::from(3), Fr::from(4)];
+        let digest = Poseidon::new_with_preimage(&preimage, &constants).hash();
+
+        let mut minimal = constants.clone();
+        minimal.sparse_matrixes = Vec::new();
+        minimal.pre_sparse_matrix = Vec::new();
+        assert!(!minimal.supports_optimized());
+        assert_eq!(Correct, minimal.preferred_hash_mode());
+        assert_eq!(
+            digest,
+            Poseidon::new_with_preimage(&preimage, &minimal)
+                .try_hash()
+                .unwrap()
+        );
+        assert_eq!(
+            digest,
+            Poseidon::new_with_preimage(&preimage, &minimal).hash_auto()
+        );
+
+        minimal.round_constants = None;
+        assert!(Poseidon::new_with_preimage(&preimage, &minimal)
+            .try_hash()
+            .is_err());
+    }
+
+    #[test]
+    fn arity_width_and_count() {
+        fn check<A: Arity<Fr>>(arity: usize) {
+            assert_eq!(arity, arity_count::<Fr, A>());
+            assert_eq!(arity + 1, arity_width::<Fr, A>());
+        }
+
+        check::<U1>(1);
+        check::<U2>(2);
+        check::<U3>(3);
+        check::<U4>(4);
+        check::<U5>(5);
+        check::<U6>(6);
+        check::<U7>(7);
+        check::<U8>(8);
+        check::<U9>(9);
+        check::<U10>(10);
+        check::<U11>(11);
+    }
+
+    #[test]
+    fn new_minimal() {
+        let constants = PoseidonConstants::<Fr, U4>::new();
+        let minimal = PoseidonConstants::<Fr, U4>::new_minimal();
+        assert!(minimal.round_constants.is_none());
+        assert!(minimal.mds_matrices.m_inv.is_empty());
+        assert!(minimal.mds_matrices.m_double_prime.is_empty());
+        assert_eq!(constants.fingerprint(), minimal.fingerprint());
+        assert!(minimal.serialized_size() < constants.serialized_size());
+
+        let preimage = [Fr::from(1), Fr::from(2), Fr::from(3), Fr::from(4)];
+        assert_eq!(
+            Poseidon::new_with_preimage(&preimage, &constants).hash(),
+            Poseidon::new_with_preimage(&preimage, &minimal).hash()
+        );
+    }
+
+    #[test]
+    fn empty_digest() {
+        fn digest<A: Arity<Fr>>() -> Fr {
+            let constants = PoseidonConstants::<Fr, A>::new();
+            let empty = super::empty_digest(&constants);
+            assert_ne!(
+                empty,
+                Poseidon::new_with_preimage(&vec![Fr::ZERO; A::to_usize()], &constants).hash()
+            );
+            assert_ne!(
+                empty,
+                super::empty_digest(&PoseidonConstants::<Fr, A>::new_with_strength(
+                    Strength::Strengthened
+                ))
+            );
+            empty
+        }
+
+        let golden: [(usize, Fr, [u64; 4]); 4] = [
+            (
+                2,
+                digest::<U2>(),
+                [
+                    0x4738195513dee85a,
+                    0x569d5c1e7535cf72,
+                    0xc9477fc74c3fffe1,
+                    0x06810a981c996758,
+                ],
+            ),
+            (
+                4,
+                digest::<U4>(),
+                [
+                    0x4f2d6ab92d24b253,
+                    0x6ac3f36089662f12,
+                    0xae1050746d0e9dcf,
+                    0x0b16bf486a718b7a,
+                ],
+            ),
+            (
+                8,
+                digest::<U8>(),
+                [
+                    0x13a6588e10f514ec,
+                    0xf40af34b31c94166,
+                    0xb08483abc4e14bd4,
+                    0x604665af39c30fff,
+                ],
+            ),
+            (
+                11,
+                digest::<U11>(),
+                [
+                    0x255279a32240c8f6,
+                    0x31da66f0a1f2dcbc,
+                    0x310c0a80c5104c57,
+                    0x4a50957f46450364,
+                ],
+            ),
+        ];
+
+        for (arity, digest, expected) in golden {
+            assert_eq!(
+                scalar_from_u64s(expected),
+                digest,
+                "empty digest changed for arity {}",
+                arity
+            );
+        }
+    }
+
+    #[test]
+    fn mds_source() {
+        let cauchy = PoseidonConstants::<Fr, U4>::try_new_with_mds_source(
+            Strength::Standard,
+            HashType::MerkleTree,
+            &MdsSource::default(),
+        )
+        .unwrap();
+        cauchy.validate().unwrap();
+        assert_eq!(PoseidonConstants::<Fr, U4>::new(), cauchy);
+
+        // Another Cauchy matrix, with x_i = i + 1 and y_j = width + j + 1.
+        let width = 5;
+        let fixed_matrix: Matrix<Fr> = (1..=width as u64)
+            .map(|x| {
+                (width as u64 + 1..=2 * width as u64)
+                    .map(|y| Fr::from(x + y).invert().unwrap())
+                    .collect()
+            })
+            .collect();
+        let fixed = PoseidonConstants::<Fr, U4>::try_new_with_mds_source(
+            Strength::Standard,
+            HashType::MerkleTree,
+            &MdsSource::Fixed(fixed_matrix.clone()),
+        )
+        .unwrap();
+        fixed.validate().unwrap();
+        assert_eq!(fixed_matrix, fixed.mds_matrices.m);
+        assert_eq!(cauchy.round_constants, fixed.round_constants);
+        assert_ne!(cauchy.fingerprint(), fixed.fingerprint());
+
+        assert!(matches!(
+            PoseidonConstants::<Fr, U2>::try_new_with_mds_source(
+                Strength::Standard,
+                HashType::MerkleTree,
+                &MdsSource::Fixed(fixed_matrix),
+            ),
+            Err(Error::MdsGenerationFailed(_))
+        ));
+    }
+
+    #[test]
+    fn dense_product_fixed_matches_generic() {
+        fn check<A: Arity<Fr>, const N: usize>() {
+            let mut rng = XorShiftRng::from_seed(crate::TEST_SEED);
+            let constants = PoseidonConstants::<Fr, A>::new();
+            let elements: Vec<Fr> = (0..N).map(|_| Fr::random(&mut rng)).collect();
+
+            for matrix in [&constants.mds_matrices.m, &constants.pre_sparse_matrix] {
+                let mut generic = vec![Fr::ZERO; N];
+                let mut fixed = vec![Fr::ONE; N];
+                dense_product(&elements, matrix, &mut generic);
+                dense_product_fixed::<Fr, N>(&elements, matrix, &mut fixed);
+                assert_eq!(generic, fixed);
+            }
+        }
+
+        check::<U4, 5>();
+        check::<U8, 9>();
+    }
+
     #[test]
     fn default_is_standard() {
         let default_constants = PoseidonConstants::<Fr, U8>::new();
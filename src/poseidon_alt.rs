@@ -18,15 +18,11 @@ where
 {
     // This counter is incremented when a round constants is read. Therefore, the round constants never repeat.
     // The first full round should use the initial constants.
-    full_round(p);
-
-    for _ in 1..p.constants.half_full_rounds {
+    for _ in 0..p.constants.half_full_rounds {
         full_round(p);
     }
 
-    partial_round(p);
-
-    for _ in 1..p.constants.partial_rounds {
+    for _ in 0..p.constants.partial_rounds {
         partial_round(p);
     }
 
@@ -105,13 +101,20 @@ where
         full_round_dynamic(p, false, true);
     }
 
-    partial_round_dynamic(p);
+    // The keys of the round following the first full rounds have already been absorbed.
+    let mut second_half_full_rounds = p.constants.half_full_rounds;
+    if p.constants.partial_rounds > 0 {
+        partial_round_dynamic(p);
+    } else {
+        full_round_dynamic(p, false, false);
+        second_half_full_rounds -= 1;
+    }
 
     for _ in 1..p.constants.partial_rounds {
         partial_round(p);
     }
 
-    for _ in 0..p.constants.half_full_rounds {
+    for _ in 0..second_half_full_rounds {
         full_round_dynamic(p, true, false);
     }
 
@@ -0,0 +1,210 @@
+//! Deterministic randomness derived from the Poseidon permutation.
+//!
+//! [`PoseidonRng`] absorbs its seed into a sponge state. Each block of output is produced by absorbing a block counter,
+//! permuting, and squeezing the rate elements of the state. Only the low [`BYTES_PER_ELEMENT`] bytes of each squeezed
+//! element are output: a uniform field element's low bits are close to uniform, while its high bits are biased by the
+//! modulus.
+use crate::bytes::scalar_to_bytes_le;
+use crate::hash_type::HashType;
+use crate::poseidon::{Arity, Poseidon, PoseidonConstants};
+use crate::Strength;
+use ff::PrimeField;
+use generic_array::typenum::U2;
+use generic_array::{sequence::GenericSequence, GenericArray};
+use rand_core::{impls, Error, RngCore, SeedableRng};
+use std::collections::VecDeque;
+
+/// Number of output bytes taken from each squeezed field element.
+pub const BYTES_PER_ELEMENT: usize = 16;
+
+/// A deterministic random number generator seeded with field elements. The same seed always produces the same
+/// stream.
+#[derive(Clone, Debug)]
+pub struct PoseidonRng<F, A = U2>
+where
+    F: PrimeField,
+    A: Arity<F>,
+{
+    constants: PoseidonConstants<F, A>,
+    state: GenericArray<F, A::ConstantsSize>,
+    counter: u64,
+    buffer: VecDeque<u8>,
+}
+
+impl<F, A> PoseidonRng<F, A>
+where
+    F: PrimeField,
+    A: Arity<F>,
+{
+    /// Creates a generator from `seed`. The length of the seed is bound into the initial state, so seeds which differ
+    /// only by trailing zeros produce different streams.
+    pub fn new(seed: &[F]) -> Self {
+        let mut rng = Self {
+            constants: PoseidonConstants::new_with_strength_and_type(
+                Strength::Standard,
+                HashType::Sponge,
+            ),
+            state: GenericArray::generate(|_| F::ZERO),
+            counter: 0,
+            buffer: VecDeque::new(),
+        };
+        rng.state[0] = F::from(seed.len() as u64);
+
+        for chunk in seed.chunks(rng.constants.arity()) {
+            for (elt, s) in rng.state[1..].iter_mut().zip(chunk) {
+                *elt += s;
+            }
+            rng.permute();
+        }
+        rng
+    }
+
+    fn permute(&mut self) {
+        let mut p = Poseidon::new(&self.constants);
+        p.elements = self.state.clone();
+        p.hash();
+        self.state = p.elements;
+    }
+
+    /// Absorbs the block counter and squeezes the rate elements into the output buffer.
+    fn refill(&mut self) {
+        self.state[1] += F::from(self.counter);
+        self.counter += 1;
+        self.permute();
+
+        for elt in &self.state[1..] {
+            self.buffer
+                .extend(&scalar_to_bytes_le(elt)[..BYTES_PER_ELEMENT]);
+        }
+    }
+}
+
+impl<F, A> RngCore for PoseidonRng<F, A>
+where
+    F: PrimeField,
+    A: Arity<F>,
+{
+    fn next_u32(&mut self) -> u32 {
+        impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for byte in dest {
+            if self.buffer.is_empty() {
+                self.refill();
+            }
+            *byte = self.buffer.pop_front().unwrap();
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+/// Seeds the generator with two field elements, read from the seed as little-endian 128-bit integers.
+impl<F, A> SeedableRng for PoseidonRng<F, A>
+where
+    F: PrimeField,
+    A: Arity<F>,
+{
+    type Seed = [u8; 32];
+
+    fn from_seed(seed: Self::Seed) -> Self {
+        let (lo, hi) = seed.split_at(16);
+        Self::new(&[
+            F::from_u128(u128::from_le_bytes(lo.try_into().unwrap())),
+            F::from_u128(u128::from_le_bytes(hi.try_into().unwrap())),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blstrs::Scalar as Fr;
+    use generic_array::typenum::U4;
+    use std::collections::HashSet;
+
+    #[test]
+    fn same_seed_same_stream() {
+        let seed = [Fr::from(1), Fr::from(2), Fr::from(3)];
+        let mut a = PoseidonRng::<Fr>::new(&seed);
+        let mut b = PoseidonRng::<Fr>::new(&seed);
+        let xs: Vec<u64> = (0..100).map(|_| a.next_u64()).collect();
+        let ys: Vec<u64> = (0..100).map(|_| b.next_u64()).collect();
+        assert_eq!(xs, ys);
+
+        let mut c = PoseidonRng::<Fr>::new(&[Fr::from(1), Fr::from(2), Fr::from(4)]);
+        let zs: Vec<u64> = (0..100).map(|_| c.next_u64()).collect();
+        assert_ne!(xs, zs);
+
+        // Trailing zeros are not ignored.
+        let mut d = PoseidonRng::<Fr>::new(&[Fr::from(1), Fr::from(2), Fr::from(3), Fr::from(0)]);
+        assert_ne!(xs[0], d.next_u64());
+
+        let mut e = PoseidonRng::<Fr, U4>::from_seed([7; 32]);
+        let mut f = PoseidonRng::<Fr, U4>::from_seed([7; 32]);
+        assert_eq!(e.next_u64(), f.next_u64());
+    }
+
+    #[test]
+    fn stream_is_independent_of_read_sizes() {
+        let seed = [Fr::from(42)];
+        let mut whole = vec![0u8; 1000];
+        PoseidonRng::<Fr>::new(&seed).fill_bytes(&mut whole);
+
+        let mut rng = PoseidonRng::<Fr>::new(&seed);
+        let mut pieces = Vec::new();
+        for size in [1, 7, 31, 32, 33, 100].iter().cycle() {
+            if pieces.len() >= whole.len() {
+                break;
+            }
+            let mut piece = vec![0u8; *size];
+            rng.fill_bytes(&mut piece);
+            pieces.extend(piece);
+        }
+        assert_eq!(whole, pieces[..whole.len()]);
+    }
+
+    #[test]
+    fn no_repeats() {
+        let mut rng = PoseidonRng::<Fr>::from_seed([0; 32]);
+        let outputs: HashSet<u64> = (0..10_000).map(|_| rng.next_u64()).collect();
+        assert_eq!(outputs.len(), 10_000);
+
+        // Every bit position is set roughly half the time.
+        let mut counts = [0u32; 64];
+        for _ in 0..10_000 {
+            let x = rng.next_u64();
+            for (i, count) in counts.iter_mut().enumerate() {
+                *count += ((x >> i) & 1) as u32;
+            }
+        }
+        assert!(counts.iter().all(|c| (4500..5500).contains(c)));
+    }
+
+    #[cfg(feature = "secp256k1")]
+    #[test]
+    fn outputs_low_bytes_of_big_endian_repr() {
+        // The k256 `Repr` is big-endian, so its leading bytes are the biased high bytes of the element.
+        let mut rng = PoseidonRng::<k256::Scalar>::new(&[k256::Scalar::from(42u64)]);
+        rng.refill();
+
+        let expected: Vec<u8> = rng.state[1..]
+            .iter()
+            .flat_map(|elt| {
+                let mut repr = elt.to_repr().to_vec();
+                repr.reverse();
+                repr.truncate(BYTES_PER_ELEMENT);
+                repr
+            })
+            .collect();
+        assert_eq!(expected, rng.buffer.iter().copied().collect::<Vec<u8>>());
+    }
+}
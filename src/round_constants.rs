@@ -64,9 +64,13 @@ pub fn generate_constants<F: PrimeField>(
                     //     random_bits = [grain_gen.next() for i in range(0, num_bits)]
                     //     random_int = int("".join(str(i) for i in random_bits), 2)
                     //     return random_int
+                    //
+                    // The bytes are then reversed for fields with a little-endian `Repr`.
                     let mut repr = F::Repr::default();
                     grain.get_next_bytes(repr.as_mut());
-                    repr.as_mut().reverse();
+                    if crate::repr_is_little_endian::<F>() {
+                        repr.as_mut().reverse();
+                    }
                     if let Some(f) = F::from_repr_vartime(repr) {
                         round_constants.push(f);
                         break;
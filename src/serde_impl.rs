@@ -6,10 +6,12 @@ use serde::{
 };
 use std::fmt;
 use std::marker::PhantomData;
+use std::sync::Arc;
 
 use crate::hash_type::HashType;
+use crate::mds::MdsMatrices;
 use crate::poseidon::PoseidonConstants;
-use crate::Arity;
+use crate::{Arity, Error, PoseidonField};
 
 impl<F, A> Serialize for PoseidonConstants<F, A>
 where
@@ -42,214 +44,426 @@ where
     where
         D: Deserializer<'de>,
     {
-        #[derive(Deserialize)]
-        #[serde(field_identifier, rename_all = "lowercase")]
-        enum Field {
-            Mds,
-            Crc,
-            Psm,
-            Sm,
-            S,
-            Rf,
-            Rp,
-            Ht,
+        deserialize_constants(deserializer, false)
+    }
+}
+
+impl<F, A> PoseidonConstants<F, A>
+where
+    F: PrimeField,
+    A: Arity<F>,
+{
+    /// Deserializes only what the default (optimized static) hash mode needs. Of the MDS matrices only `m` is kept;
+    /// the others are read and discarded without being collected, and are left empty in the result. The resulting
+    /// constants produce the same digests as fully deserialized constants in the default hash mode, but must not be
+    /// used with other hash modes or to derive further constants.
+    pub fn deserialize_hashing_only<'de, D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        F: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        deserialize_constants(deserializer, true)
+    }
+
+    /// Returns the exact length of the bincode serialization of `self`, as written by `bincode::serialize` and in the
+    /// body of [`crate::versioned::write_constants`]. The length is computed without serializing.
+    pub fn serialized_size(&self) -> usize
+    where
+        F: PoseidonField,
+    {
+        bincode::serialized_size(self).expect("constants can always be serialized") as usize
+    }
+
+    /// Deserializes the bincode serialization of constants into an [`Arc`], for sharing between hashers and threads.
+    /// This is `bincode::deserialize` followed by [`Arc::new`], with the error mapped to [`Error::Format`].
+    pub fn deserialize_arc(bytes: &[u8]) -> Result<Arc<Self>, Error>
+    where
+        F: PoseidonField,
+    {
+        bincode::deserialize(bytes)
+            .map(Arc::new)
+            .map_err(|e| Error::Format(e.to_string()))
+    }
+}
+
+#[cfg(feature = "cbor")]
+impl<F, A> PoseidonConstants<F, A>
+where
+    F: PrimeField,
+    A: Arity<F>,
+{
+    /// Serializes to CBOR. Field elements are encoded by their own `Serialize` implementation in its compact form, so
+    /// they hold the same raw values as in the bincode encoding.
+    pub fn to_cbor(&self) -> Result<Vec<u8>, Error>
+    where
+        F: PoseidonField,
+    {
+        serde_cbor::to_vec(self).map_err(|e| Error::Format(e.to_string()))
+    }
+
+    /// Deserializes from CBOR written by [`PoseidonConstants::to_cbor`]. As with other formats, the result holds only
+    /// the compressed round constants.
+    pub fn from_cbor(bytes: &[u8]) -> Result<Self, Error>
+    where
+        F: PoseidonField,
+    {
+        serde_cbor::from_slice(bytes).map_err(|e| Error::Format(e.to_string()))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "lowercase")]
+enum Field {
+    Mds,
+    Crc,
+    Psm,
+    Sm,
+    S,
+    Rf,
+    Rp,
+    Ht,
+}
+
+const FIELDS: &[&str] = &[
+    "mds_matrices",
+    "compressed_round_constants",
+    "pre_sparse_matrix",
+    "sparse_matrixes",
+    "strength",
+    "full_rounds",
+    "partial_rounds",
+    "hash_type",
+];
+
+fn deserialize_constants<'de, D, F, A>(
+    deserializer: D,
+    hashing_only: bool,
+) -> Result<PoseidonConstants<F, A>, D::Error>
+where
+    D: Deserializer<'de>,
+    F: PrimeField + Deserialize<'de>,
+    A: Arity<F>,
+{
+    deserializer.deserialize_struct(
+        "PoseidonConstants",
+        FIELDS,
+        PoseidonConstantsVisitor {
+            hashing_only,
+            _f: PhantomData,
+            _a: PhantomData,
+        },
+    )
+}
+
+struct PoseidonConstantsVisitor<F, A>
+where
+    F: PrimeField,
+    A: Arity<F>,
+{
+    hashing_only: bool,
+    _f: PhantomData<F>,
+    _a: PhantomData<A>,
+}
+
+impl<'de, F, A> Visitor<'de> for PoseidonConstantsVisitor<F, A>
+where
+    F: PrimeField + Deserialize<'de>,
+    A: Arity<F>,
+{
+    type Value = PoseidonConstants<F, A>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("struct PoseidonConstants")
+    }
+
+    fn visit_seq<V>(self, mut seq: V) -> Result<PoseidonConstants<F, A>, V::Error>
+    where
+        V: SeqAccess<'de>,
+    {
+        let mds_matrices = if self.hashing_only {
+            seq.next_element::<HashingMds<F>>()?.map(|mds| mds.0)
+        } else {
+            seq.next_element()?
         }
+        .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let compressed_round_constants = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        let pre_sparse_matrix = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(2, &self))?;
+        let sparse_matrixes = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(3, &self))?;
+        let strength = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(4, &self))?;
+        let full_rounds = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(5, &self))?;
+        let partial_rounds = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(6, &self))?;
+        let hash_type: HashType<F, A> = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(7, &self))?;
 
-        struct PoseidonConstantsVisitor<F, A>
-        where
-            F: PrimeField,
-            A: Arity<F>,
-        {
-            _f: PhantomData<F>,
-            _a: PhantomData<A>,
+        let constants = PoseidonConstants {
+            mds_matrices,
+            round_constants: None,
+            compressed_round_constants,
+            pre_sparse_matrix,
+            sparse_matrixes,
+            strength,
+            domain_tag: hash_type.domain_tag_with_strength(strength),
+            full_rounds,
+            half_full_rounds: full_rounds / 2,
+            partial_rounds,
+            hash_type,
+            _a: PhantomData::<A>,
+        };
+        constants
+            .check_round_constants_len()
+            .map_err(de::Error::custom)?;
+        Ok(constants)
+    }
+
+    fn visit_map<V>(self, mut map: V) -> Result<PoseidonConstants<F, A>, V::Error>
+    where
+        V: MapAccess<'de>,
+    {
+        let mut mds_matrices = None;
+        let mut compressed_round_constants = None;
+        let mut pre_sparse_matrix = None;
+        let mut sparse_matrixes = None;
+        let mut strength = None;
+        let mut full_rounds = None;
+        let mut partial_rounds = None;
+        let mut hash_type = None;
+
+        while let Some(key) = map.next_key()? {
+            match key {
+                Field::Mds => {
+                    if mds_matrices.is_some() {
+                        return Err(de::Error::duplicate_field("mds_matrices"));
+                    }
+                    mds_matrices = Some(if self.hashing_only {
+                        map.next_value::<HashingMds<F>>()?.0
+                    } else {
+                        map.next_value()?
+                    });
+                }
+                Field::Crc => {
+                    if compressed_round_constants.is_some() {
+                        return Err(de::Error::duplicate_field("compressed_round_constants"));
+                    }
+                    compressed_round_constants = Some(map.next_value()?);
+                }
+                Field::Psm => {
+                    if pre_sparse_matrix.is_some() {
+                        return Err(de::Error::duplicate_field("pre_sparse_matrix"));
+                    }
+                    pre_sparse_matrix = Some(map.next_value()?);
+                }
+                Field::Sm => {
+                    if sparse_matrixes.is_some() {
+                        return Err(de::Error::duplicate_field("sparse_matrixes"));
+                    }
+                    sparse_matrixes = Some(map.next_value()?);
+                }
+                Field::S => {
+                    if strength.is_some() {
+                        return Err(de::Error::duplicate_field("strength"));
+                    }
+                    strength = Some(map.next_value()?);
+                }
+                Field::Rf => {
+                    if full_rounds.is_some() {
+                        return Err(de::Error::duplicate_field("full_rounds"));
+                    }
+                    full_rounds = Some(map.next_value()?);
+                }
+                Field::Rp => {
+                    if partial_rounds.is_some() {
+                        return Err(de::Error::duplicate_field("partial_rounds"));
+                    }
+                    partial_rounds = Some(map.next_value()?);
+                }
+                Field::Ht => {
+                    if hash_type.is_some() {
+                        return Err(de::Error::duplicate_field("hash_type"));
+                    }
+                    hash_type = Some(map.next_value()?);
+                }
+            }
         }
 
-        impl<'de, F, A> Visitor<'de> for PoseidonConstantsVisitor<F, A>
+        let mds_matrices = mds_matrices.ok_or_else(|| de::Error::missing_field("mds_matrices"))?;
+        let compressed_round_constants = compressed_round_constants
+            .ok_or_else(|| de::Error::missing_field("compressed_round_constants"))?;
+        let pre_sparse_matrix =
+            pre_sparse_matrix.ok_or_else(|| de::Error::missing_field("pre_sparse_matrix"))?;
+        let sparse_matrixes =
+            sparse_matrixes.ok_or_else(|| de::Error::missing_field("sparse_matrixes"))?;
+        let strength = strength.ok_or_else(|| de::Error::missing_field("strength"))?;
+        let full_rounds = full_rounds.ok_or_else(|| de::Error::missing_field("full_rounds"))?;
+        let partial_rounds =
+            partial_rounds.ok_or_else(|| de::Error::missing_field("partial_rounds"))?;
+        let hash_type: HashType<F, A> =
+            hash_type.ok_or_else(|| de::Error::missing_field("hash_type"))?;
+        let constants = PoseidonConstants {
+            mds_matrices,
+            round_constants: None,
+            compressed_round_constants,
+            pre_sparse_matrix,
+            sparse_matrixes,
+            strength,
+            domain_tag: hash_type.domain_tag_with_strength(strength),
+            full_rounds,
+            half_full_rounds: full_rounds / 2,
+            partial_rounds,
+            hash_type,
+            _a: PhantomData::<A>,
+        };
+        constants
+            .check_round_constants_len()
+            .map_err(de::Error::custom)?;
+        Ok(constants)
+    }
+}
+
+/// [`MdsMatrices`] with only `m`, the matrix used by the optimized hash, deserialized.
+struct HashingMds<F: PrimeField>(MdsMatrices<F>);
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "snake_case")]
+enum MdsField {
+    M,
+    MInv,
+    MHat,
+    MHatInv,
+    MPrime,
+    MDoublePrime,
+}
+
+const MDS_FIELDS: &[&str] = &[
+    "m",
+    "m_inv",
+    "m_hat",
+    "m_hat_inv",
+    "m_prime",
+    "m_double_prime",
+];
+
+impl<'de, F> Deserialize<'de> for HashingMds<F>
+where
+    F: PrimeField + Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct HashingMdsVisitor<F>(PhantomData<F>);
+
+        impl<'de, F> Visitor<'de> for HashingMdsVisitor<F>
         where
             F: PrimeField + Deserialize<'de>,
-            A: Arity<F>,
         {
-            type Value = PoseidonConstants<F, A>;
+            type Value = HashingMds<F>;
 
             fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-                formatter.write_str("struct PoseidonConstants")
+                formatter.write_str("struct MdsMatrices")
             }
 
-            fn visit_seq<V>(self, mut seq: V) -> Result<PoseidonConstants<F, A>, V::Error>
+            fn visit_seq<V>(self, mut seq: V) -> Result<HashingMds<F>, V::Error>
             where
                 V: SeqAccess<'de>,
             {
-                let mds_matrices = seq
+                let m = seq
                     .next_element()?
                     .ok_or_else(|| de::Error::invalid_length(0, &self))?;
-                let compressed_round_constants = seq
-                    .next_element()?
-                    .ok_or_else(|| de::Error::invalid_length(1, &self))?;
-                let pre_sparse_matrix = seq
-                    .next_element()?
-                    .ok_or_else(|| de::Error::invalid_length(2, &self))?;
-                let sparse_matrixes = seq
-                    .next_element()?
-                    .ok_or_else(|| de::Error::invalid_length(3, &self))?;
-                let strength = seq
-                    .next_element()?
-                    .ok_or_else(|| de::Error::invalid_length(4, &self))?;
-                let full_rounds = seq
-                    .next_element()?
-                    .ok_or_else(|| de::Error::invalid_length(5, &self))?;
-                let partial_rounds = seq
-                    .next_element()?
-                    .ok_or_else(|| de::Error::invalid_length(6, &self))?;
-                let hash_type: HashType<F, A> = seq
-                    .next_element()?
-                    .ok_or_else(|| de::Error::invalid_length(7, &self))?;
-
-                Ok(PoseidonConstants {
-                    mds_matrices,
-                    round_constants: None,
-                    compressed_round_constants,
-                    pre_sparse_matrix,
-                    sparse_matrixes,
-                    strength,
-                    domain_tag: hash_type.domain_tag(),
-                    full_rounds,
-                    half_full_rounds: full_rounds / 2,
-                    partial_rounds,
-                    hash_type,
-                    _a: PhantomData::<A>,
-                })
+                for i in 1..MDS_FIELDS.len() {
+                    seq.next_element::<SkippedSeq<SkippedSeq<F>>>()?
+                        .ok_or_else(|| de::Error::invalid_length(i, &self))?;
+                }
+                Ok(HashingMds(MdsMatrices::only_m(m)))
             }
 
-            fn visit_map<V>(self, mut map: V) -> Result<PoseidonConstants<F, A>, V::Error>
+            fn visit_map<V>(self, mut map: V) -> Result<HashingMds<F>, V::Error>
             where
                 V: MapAccess<'de>,
             {
-                let mut mds_matrices = None;
-                let mut compressed_round_constants = None;
-                let mut pre_sparse_matrix = None;
-                let mut sparse_matrixes = None;
-                let mut strength = None;
-                let mut full_rounds = None;
-                let mut partial_rounds = None;
-                let mut hash_type = None;
-
+                let mut m = None;
                 while let Some(key) = map.next_key()? {
                     match key {
-                        Field::Mds => {
-                            if mds_matrices.is_some() {
-                                return Err(de::Error::duplicate_field("mds_matrices"));
-                            }
-                            mds_matrices = Some(map.next_value()?);
-                        }
-                        Field::Crc => {
-                            if compressed_round_constants.is_some() {
-                                return Err(de::Error::duplicate_field(
-                                    "compressed_round_constants",
-                                ));
-                            }
-                            compressed_round_constants = Some(map.next_value()?);
-                        }
-                        Field::Psm => {
-                            if pre_sparse_matrix.is_some() {
-                                return Err(de::Error::duplicate_field("pre_sparse_matrix"));
-                            }
-                            pre_sparse_matrix = Some(map.next_value()?);
-                        }
-                        Field::Sm => {
-                            if sparse_matrixes.is_some() {
-                                return Err(de::Error::duplicate_field("sparse_matrixes"));
+                        MdsField::M => {
+                            if m.is_some() {
+                                return Err(de::Error::duplicate_field("m"));
                             }
-                            sparse_matrixes = Some(map.next_value()?);
+                            m = Some(map.next_value()?);
                         }
-                        Field::S => {
-                            if strength.is_some() {
-                                return Err(de::Error::duplicate_field("strength"));
-                            }
-                            strength = Some(map.next_value()?);
-                        }
-                        Field::Rf => {
-                            if full_rounds.is_some() {
-                                return Err(de::Error::duplicate_field("full_rounds"));
-                            }
-                            full_rounds = Some(map.next_value()?);
-                        }
-                        Field::Rp => {
-                            if partial_rounds.is_some() {
-                                return Err(de::Error::duplicate_field("partial_rounds"));
-                            }
-                            partial_rounds = Some(map.next_value()?);
-                        }
-                        Field::Ht => {
-                            if hash_type.is_some() {
-                                return Err(de::Error::duplicate_field("hash_type"));
-                            }
-                            hash_type = Some(map.next_value()?);
+                        _ => {
+                            map.next_value::<SkippedSeq<SkippedSeq<F>>>()?;
                         }
                     }
                 }
+                let m = m.ok_or_else(|| de::Error::missing_field("m"))?;
+                Ok(HashingMds(MdsMatrices::only_m(m)))
+            }
+        }
+
+        deserializer.deserialize_struct("MdsMatrices", MDS_FIELDS, HashingMdsVisitor(PhantomData))
+    }
+}
+
+/// A sequence of `T` which is deserialized element by element and discarded.
+struct SkippedSeq<T>(PhantomData<T>);
+
+impl<'de, T> Deserialize<'de> for SkippedSeq<T>
+where
+    T: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct SkippedSeqVisitor<T>(PhantomData<T>);
+
+        impl<'de, T> Visitor<'de> for SkippedSeqVisitor<T>
+        where
+            T: Deserialize<'de>,
+        {
+            type Value = SkippedSeq<T>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a sequence")
+            }
 
-                let mds_matrices =
-                    mds_matrices.ok_or_else(|| de::Error::missing_field("mds_matrices"))?;
-                let compressed_round_constants = compressed_round_constants
-                    .ok_or_else(|| de::Error::missing_field("compressed_round_constants"))?;
-                let pre_sparse_matrix = pre_sparse_matrix
-                    .ok_or_else(|| de::Error::missing_field("pre_sparse_matrix"))?;
-                let sparse_matrixes =
-                    sparse_matrixes.ok_or_else(|| de::Error::missing_field("sparse_matrixes"))?;
-                let strength = strength.ok_or_else(|| de::Error::missing_field("strength"))?;
-                let full_rounds =
-                    full_rounds.ok_or_else(|| de::Error::missing_field("full_rounds"))?;
-                let partial_rounds =
-                    partial_rounds.ok_or_else(|| de::Error::missing_field("partial_rounds"))?;
-                let hash_type: HashType<F, A> =
-                    hash_type.ok_or_else(|| de::Error::missing_field("hash_type"))?;
-                Ok(PoseidonConstants {
-                    mds_matrices,
-                    round_constants: None,
-                    compressed_round_constants,
-                    pre_sparse_matrix,
-                    sparse_matrixes,
-                    strength,
-                    domain_tag: hash_type.domain_tag(),
-                    full_rounds,
-                    half_full_rounds: full_rounds / 2,
-                    partial_rounds,
-                    hash_type,
-                    _a: PhantomData::<A>,
-                })
+            fn visit_seq<V>(self, mut seq: V) -> Result<SkippedSeq<T>, V::Error>
+            where
+                V: SeqAccess<'de>,
+            {
+                while seq.next_element::<T>()?.is_some() {}
+                Ok(SkippedSeq(PhantomData))
             }
         }
 
-        const FIELDS: &[&str] = &[
-            "mds_matrices",
-            "compressed_round_constants",
-            "pre_sparse_matrix",
-            "sparse_matrixes",
-            "strength",
-            "full_rounds",
-            "partial_rounds",
-            "hash_type",
-        ];
-        deserializer.deserialize_struct(
-            "PoseidonConstants",
-            FIELDS,
-            PoseidonConstantsVisitor {
-                _f: PhantomData,
-                _a: PhantomData,
-            },
-        )
+        deserializer.deserialize_seq(SkippedSeqVisitor(PhantomData))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::hash_type::CType;
+    use crate::poseidon::hash_in_place;
     use crate::Poseidon;
     use blstrs::Scalar as Fr;
     use ff::Field;
-    use generic_array::typenum;
+    use generic_array::sequence::GenericSequence;
+    use generic_array::{typenum, GenericArray};
     use pasta_curves::pallas::Scalar as S1;
-    use typenum::{U1, U2};
+    use typenum::{U1, U2, U4};
 
     #[test]
     fn serde_roundtrip() {
@@ -266,6 +480,82 @@ mod tests {
         );
     }
 
+    #[test]
+    fn deserialize_released_bincode() {
+        // Written with `bincode::serialize` by neptune 10.0.0, so the layout must not change.
+        let released = include_bytes!("../parameters/constants-bls12_381-arity2-v10.0.0.bincode");
+
+        let mut constants = PoseidonConstants::<Fr, U2>::new();
+        constants.round_constants = None;
+        let decoded: PoseidonConstants<Fr, U2> = bincode::deserialize(released).unwrap();
+        assert_eq!(constants, decoded);
+        assert_eq!(released.to_vec(), bincode::serialize(&constants).unwrap());
+    }
+
+    fn assert_roundtrips_for_all_hash_types<A: Arity<Fr> + PartialEq>() {
+        let hash_types = [
+            HashType::MerkleTree,
+            HashType::VariableLength,
+            HashType::ConstantLength(1),
+            HashType::ConstantLength(A::to_usize()),
+            HashType::Encryption,
+            HashType::Custom(CType::Arbitrary(1)),
+            HashType::Custom(CType::Arbitrary(256)),
+        ];
+
+        for hash_type in hash_types {
+            // `VariableLength` is not supported by the constructors, but its constants can still be serialized.
+            let base = PoseidonConstants::<Fr, A>::new();
+            let constants = PoseidonConstants {
+                domain_tag: hash_type.domain_tag(),
+                hash_type,
+                ..base
+            };
+
+            let from_bincode: PoseidonConstants<Fr, A> =
+                bincode::deserialize(&bincode::serialize(&constants).unwrap()).unwrap();
+            let from_json: PoseidonConstants<Fr, A> =
+                serde_json::from_slice(&serde_json::to_vec(&constants).unwrap()).unwrap();
+
+            let mut state = GenericArray::<Fr, A::ConstantsSize>::generate(|i| Fr::from(i as u64));
+            state[0] = constants.domain_tag;
+            let digest = hash_in_place(&constants, &mut state.clone());
+            for recovered in [from_bincode, from_json] {
+                assert_eq!(constants.hash_type, recovered.hash_type);
+                assert_eq!(constants.fingerprint(), recovered.fingerprint());
+                assert_eq!(digest, hash_in_place(&recovered, &mut state.clone()));
+            }
+        }
+    }
+
+    #[test]
+    fn serde_roundtrip_all_hash_types() {
+        assert_roundtrips_for_all_hash_types::<U2>();
+        assert_roundtrips_for_all_hash_types::<U4>();
+    }
+
+    #[test]
+    fn deserialize_arc() {
+        let constants = PoseidonConstants::<Fr, U2>::new();
+        let bytes = bincode::serialize(&constants).unwrap();
+
+        let shared = PoseidonConstants::<Fr, U2>::deserialize_arc(&bytes).unwrap();
+        let other = Arc::clone(&shared);
+        assert!(Arc::ptr_eq(&shared, &other));
+        assert_eq!(2, Arc::strong_count(&shared));
+
+        let preimage = [Fr::ONE, Fr::ONE];
+        assert_eq!(
+            Poseidon::new_with_preimage(&preimage, &constants).hash(),
+            Poseidon::new_with_preimage(&preimage, &other).hash()
+        );
+
+        assert!(matches!(
+            PoseidonConstants::<Fr, U2>::deserialize_arc(&bytes[..bytes.len() - 1]),
+            Err(Error::Format(_))
+        ));
+    }
+
     #[test]
     fn serde_hash_blstrs() {
         let constants = PoseidonConstants::<Fr, U2>::new();
@@ -298,4 +588,137 @@ mod tests {
         h1.set_preimage(&preimage); // reset
         assert_eq!(h1.hash(), h3.hash());
     }
+
+    #[cfg(feature = "secp256k1")]
+    #[test]
+    fn serde_hash_secp256k1() {
+        use k256::Scalar as K;
+
+        let constants = PoseidonConstants::<K, U2>::new();
+        let constants2 = bincode::deserialize(&bincode::serialize(&constants).unwrap()).unwrap();
+        let constants3 = serde_json::from_slice(&serde_json::to_vec(&constants).unwrap()).unwrap();
+        let test_arity = 2;
+        let preimage = vec![<K as Field>::ONE; test_arity];
+        let mut h1 = Poseidon::<K, U2>::new_with_preimage(&preimage, &constants);
+        let mut h2 = Poseidon::<K, U2>::new_with_preimage(&preimage, &constants2);
+        let mut h3 = Poseidon::<K, U2>::new_with_preimage(&preimage, &constants3);
+
+        assert_eq!(h1.hash(), h2.hash());
+        h1.set_preimage(&preimage); // reset
+        assert_eq!(h1.hash(), h3.hash());
+    }
+
+    #[cfg(feature = "bn254")]
+    #[test]
+    fn serde_hash_bn254() {
+        use halo2curves::bn256::Fr as B;
+
+        let constants = PoseidonConstants::<B, U2>::new();
+        let constants2 = bincode::deserialize(&bincode::serialize(&constants).unwrap()).unwrap();
+        let constants3 = serde_json::from_slice(&serde_json::to_vec(&constants).unwrap()).unwrap();
+        let test_arity = 2;
+        let preimage = vec![<B as Field>::ONE; test_arity];
+        let mut h1 = Poseidon::<B, U2>::new_with_preimage(&preimage, &constants);
+        let mut h2 = Poseidon::<B, U2>::new_with_preimage(&preimage, &constants2);
+        let mut h3 = Poseidon::<B, U2>::new_with_preimage(&preimage, &constants3);
+
+        assert_eq!(h1.hash(), h2.hash());
+        h1.set_preimage(&preimage); // reset
+        assert_eq!(h1.hash(), h3.hash());
+    }
+
+    #[test]
+    fn deserialize_hashing_only() {
+        use bincode::Options;
+
+        let constants = PoseidonConstants::<Fr, U2>::new();
+        let preimage = [Fr::from(1), Fr::from(2)];
+        let expected = Poseidon::new_with_preimage(&preimage, &constants).hash();
+
+        // Matches the options used by `bincode::serialize`.
+        let bytes = bincode::serialize(&constants).unwrap();
+        let options = bincode::DefaultOptions::new()
+            .with_fixint_encoding()
+            .allow_trailing_bytes();
+        let from_bincode = PoseidonConstants::<Fr, U2>::deserialize_hashing_only(
+            &mut bincode::Deserializer::from_slice(&bytes, options),
+        )
+        .unwrap();
+
+        let json = serde_json::to_vec(&constants).unwrap();
+        let from_json = PoseidonConstants::<Fr, U2>::deserialize_hashing_only(
+            &mut serde_json::Deserializer::from_slice(&json),
+        )
+        .unwrap();
+
+        for minimal in [from_bincode, from_json] {
+            assert_eq!(constants.mds_matrices.m, minimal.mds_matrices.m);
+            assert!(minimal.mds_matrices.m_inv.is_empty());
+            assert_eq!(
+                expected,
+                Poseidon::new_with_preimage(&preimage, &minimal).hash()
+            );
+        }
+    }
+
+    #[test]
+    fn serialized_size_is_exact() {
+        let constants = PoseidonConstants::<Fr, typenum::U4>::new();
+        assert_eq!(
+            bincode::serialize(&constants).unwrap().len(),
+            constants.serialized_size()
+        );
+    }
+
+    #[test]
+    fn deserialize_rejects_truncated_round_constants() {
+        let mut constants = PoseidonConstants::<Fr, U2>::new();
+        constants.round_constants = None;
+        constants.compressed_round_constants.pop();
+        assert!(matches!(
+            constants.check_round_constants_len(),
+            Err(crate::Error::InsufficientRoundConstants { .. })
+        ));
+
+        let err = bincode::deserialize::<PoseidonConstants<Fr, U2>>(
+            &bincode::serialize(&constants).unwrap(),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("Insufficient round constants"));
+
+        let err = serde_json::from_slice::<PoseidonConstants<Fr, U2>>(
+            &serde_json::to_vec(&constants).unwrap(),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("Insufficient round constants"));
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn serde_cbor_roundtrip() {
+        let mut constants = PoseidonConstants::<S1, U2>::new();
+        constants.round_constants = None;
+        assert_eq!(
+            constants,
+            PoseidonConstants::from_cbor(&constants.to_cbor().unwrap()).unwrap()
+        );
+
+        let constants = PoseidonConstants::<Fr, U2>::new();
+        let from_cbor =
+            PoseidonConstants::<Fr, U2>::from_cbor(&constants.to_cbor().unwrap()).unwrap();
+        let from_bincode: PoseidonConstants<Fr, U2> =
+            bincode::deserialize(&bincode::serialize(&constants).unwrap()).unwrap();
+        assert_eq!(from_bincode, from_cbor);
+
+        let preimage = vec![<Fr as Field>::ONE; 2];
+        assert_eq!(
+            Poseidon::new_with_preimage(&preimage, &constants).hash(),
+            Poseidon::new_with_preimage(&preimage, &from_cbor).hash()
+        );
+
+        assert!(matches!(
+            PoseidonConstants::<Fr, U2>::from_cbor(&[0xff, 0x00]),
+            Err(Error::Format(_))
+        ));
+    }
 }
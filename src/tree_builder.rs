@@ -92,12 +92,26 @@ where
         tree_batcher: Option<Batcher<F, TreeArity>>,
         leaf_count: usize,
         rows_to_discard: usize,
+    ) -> Result<Self, Error> {
+        Self::new_with_constants(
+            tree_batcher,
+            leaf_count,
+            rows_to_discard,
+            PoseidonConstants::<F, TreeArity>::new(),
+        )
+    }
+
+    pub fn new_with_constants(
+        tree_batcher: Option<Batcher<F, TreeArity>>,
+        leaf_count: usize,
+        rows_to_discard: usize,
+        tree_constants: PoseidonConstants<F, TreeArity>,
     ) -> Result<Self, Error> {
         let builder = Self {
             leaf_count,
             data: vec![F::ZERO; leaf_count],
             fill_index: 0,
-            tree_constants: PoseidonConstants::<F, TreeArity>::new(),
+            tree_constants,
             tree_batcher,
             rows_to_discard,
         };
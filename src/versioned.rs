@@ -0,0 +1,438 @@
+//! Versioned binary encoding of [`PoseidonConstants`].
+//!
+//! Encoded constants consist of a fixed-size header followed by a body. The header holds the magic bytes [`MAGIC`],
+//! the format version as a little-endian `u16`, the [`field_id`] of the field and the length of the body in bytes as a
+//! little-endian `u64`. The body is the bincode serialization of the constants, followed by that of an `Option<F>`
+//! holding the domain tag if it is not the one derived from the hash type and strength, as for constants from
+//! [`PoseidonConstants::new_with_domain_tag`]. The serde implementations of [`PoseidonConstants`] keep the layout of
+//! released versions and always rederive the tag, so the tag is only preserved by this encoding.
+//!
+//! Version 2 bodies lack the domain tag and are read with the derived tag. Version 1 had no field identifier. Its
+//! encodings are rejected, as they cannot be checked against the field.
+use crate::poseidon::{Arity, PoseidonConstants};
+use crate::{Error, PoseidonField};
+use byteorder::{ByteOrder, LittleEndian};
+use ff::PrimeField;
+use std::io::{self, Read, Write};
+
+/// Magic bytes identifying encoded constants.
+pub const MAGIC: [u8; 4] = *b"NPTN";
+
+/// Current format version.
+pub const FORMAT_VERSION: u16 = 3;
+
+/// Oldest format version which can be read.
+const MIN_SUPPORTED_VERSION: u16 = 2;
+
+/// Size of the header in bytes.
+pub const HEADER_LEN: usize = 22;
+
+/// Identifies the field `F` by the first 8 bytes of the Blake2s hash of its modulus. Fields of the same size, such as
+/// the BLS12-381 scalar field and the Pallas base field, have encodings of the same shape, which this tells apart.
+/// Different implementations of the same field share an identifier.
+pub fn field_id<F: PrimeField>() -> [u8; 8] {
+    let hash = blake2s_simd::Params::new()
+        .hash_length(8)
+        .hash(F::MODULUS.as_bytes());
+    let mut id = [0u8; 8];
+    id.copy_from_slice(hash.as_bytes());
+    id
+}
+
+/// The fixed-size header preceding the body of encoded constants.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Header {
+    pub version: u16,
+    pub field_id: [u8; 8],
+    pub body_len: u64,
+}
+
+impl Header {
+    pub fn to_bytes(&self) -> [u8; HEADER_LEN] {
+        let mut bytes = [0u8; HEADER_LEN];
+        bytes[..4].copy_from_slice(&MAGIC);
+        LittleEndian::write_u16(&mut bytes[4..6], self.version);
+        bytes[6..14].copy_from_slice(&self.field_id);
+        LittleEndian::write_u64(&mut bytes[14..], self.body_len);
+        bytes
+    }
+
+    /// Parses a header. Returns [`Error::UnsupportedVersion`] for versions newer than [`FORMAT_VERSION`], and
+    /// [`Error::Format`] if the magic bytes are wrong or the version is too old to be read.
+    pub fn from_bytes(bytes: &[u8; HEADER_LEN]) -> Result<Self, Error> {
+        let version = peek_version(bytes)?;
+        if version > FORMAT_VERSION {
+            return Err(Error::UnsupportedVersion {
+                found: version,
+                max_supported: FORMAT_VERSION,
+            });
+        }
+        if version < MIN_SUPPORTED_VERSION {
+            return Err(Error::Format(format!("unsupported version {version}")));
+        }
+
+        let mut field_id = [0u8; 8];
+        field_id.copy_from_slice(&bytes[6..14]);
+
+        Ok(Self {
+            version,
+            field_id,
+            body_len: LittleEndian::read_u64(&bytes[14..]),
+        })
+    }
+}
+
+/// Returns the format version of encoded constants, reading only the magic bytes and the version, so that tools can
+/// report compatibility without decoding. Returns [`Error::Format`] if `bytes` is too short or does not start with the
+/// magic bytes.
+pub fn peek_version(bytes: &[u8]) -> Result<u16, Error> {
+    if bytes.len() < 6 {
+        return Err(Error::Format("unexpected end of data".to_string()));
+    }
+    if bytes[..4] != MAGIC {
+        return Err(Error::Format("missing magic bytes".to_string()));
+    }
+    Ok(LittleEndian::read_u16(&bytes[4..6]))
+}
+
+/// Writes `constants` to `writer`, header first.
+pub fn write_constants<F, A, W>(
+    constants: &PoseidonConstants<F, A>,
+    mut writer: W,
+) -> Result<(), Error>
+where
+    F: PoseidonField,
+    A: Arity<F>,
+    W: Write,
+{
+    let body = bincode::serialize(&(constants, custom_domain_tag(constants)))
+        .map_err(|e| Error::Format(format!("cannot serialize constants: {e}")))?;
+    let header = Header {
+        version: FORMAT_VERSION,
+        field_id: field_id::<F>(),
+        body_len: body.len() as u64,
+    };
+
+    writer.write_all(&header.to_bytes()).map_err(io_error)?;
+    writer.write_all(&body).map_err(io_error)?;
+    Ok(())
+}
+
+/// Returns the number of bytes [`write_constants`] writes for `constants`, header included.
+pub fn encoded_len<F, A>(constants: &PoseidonConstants<F, A>) -> usize
+where
+    F: PoseidonField,
+    A: Arity<F>,
+{
+    let tag_len = bincode::serialized_size(&custom_domain_tag(constants))
+        .expect("field elements can always be serialized") as usize;
+    HEADER_LEN + constants.serialized_size() + tag_len
+}
+
+/// Returns the domain tag of `constants` if it is not the one derived from their hash type and strength.
+fn custom_domain_tag<F, A>(constants: &PoseidonConstants<F, A>) -> Option<F>
+where
+    F: PrimeField,
+    A: Arity<F>,
+{
+    let derived = constants
+        .hash_type
+        .domain_tag_with_strength(constants.strength);
+    (constants.domain_tag != derived).then_some(constants.domain_tag)
+}
+
+/// Reads constants written by [`write_constants`] from `reader`. Failures of the reader are reported as
+/// [`Error::Io`], while malformed or truncated data is reported as [`Error::Format`]. Constants written for a field
+/// other than `F` are reported as [`Error::FieldMismatch`].
+pub fn read_constants<F, A, R>(mut reader: R) -> Result<PoseidonConstants<F, A>, Error>
+where
+    F: PoseidonField,
+    A: Arity<F>,
+    R: Read,
+{
+    let mut header = [0u8; HEADER_LEN];
+    reader.read_exact(&mut header).map_err(io_error)?;
+    let header = Header::from_bytes(&header)?;
+
+    let mut body = Vec::new();
+    reader
+        .take(header.body_len)
+        .read_to_end(&mut body)
+        .map_err(io_error)?;
+
+    deserialize_body(&header, &body)
+}
+
+/// Asynchronous version of [`read_constants`]. Partial reads are handled by reading until the header and the body
+/// are complete.
+#[cfg(feature = "tokio")]
+pub async fn load_constants_async<F, A, R>(mut reader: R) -> Result<PoseidonConstants<F, A>, Error>
+where
+    F: PoseidonField,
+    A: Arity<F>,
+    R: tokio::io::AsyncRead + Unpin,
+{
+    use tokio::io::AsyncReadExt;
+
+    let mut header = [0u8; HEADER_LEN];
+    reader.read_exact(&mut header).await.map_err(io_error)?;
+    let header = Header::from_bytes(&header)?;
+
+    let mut body = Vec::new();
+    reader
+        .take(header.body_len)
+        .read_to_end(&mut body)
+        .await
+        .map_err(io_error)?;
+
+    deserialize_body(&header, &body)
+}
+
+fn deserialize_body<F, A>(header: &Header, body: &[u8]) -> Result<PoseidonConstants<F, A>, Error>
+where
+    F: PoseidonField,
+    A: Arity<F>,
+{
+    if header.field_id != field_id::<F>() {
+        return Err(Error::FieldMismatch);
+    }
+    if body.len() as u64 != header.body_len {
+        return Err(Error::Format(format!(
+            "truncated body: expected {} bytes, got {}",
+            header.body_len,
+            body.len()
+        )));
+    }
+
+    let invalid_body = |e: bincode::Error| Error::Format(format!("invalid body: {e}"));
+    if header.version == 2 {
+        return bincode::deserialize(body).map_err(invalid_body);
+    }
+
+    let (mut constants, domain_tag): (PoseidonConstants<F, A>, Option<F>) =
+        bincode::deserialize(body).map_err(invalid_body)?;
+    if let Some(domain_tag) = domain_tag {
+        constants.domain_tag = domain_tag;
+    }
+    Ok(constants)
+}
+
+/// Running out of data is a property of the data rather than of the reader, so it is reported as a format error.
+fn io_error(e: io::Error) -> Error {
+    match e.kind() {
+        io::ErrorKind::UnexpectedEof => Error::Format("unexpected end of data".to_string()),
+        _ => Error::Io(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Poseidon;
+    use blstrs::Scalar as Fr;
+    use ff::Field;
+    use generic_array::typenum::U2;
+    use pasta_curves::Fp;
+
+    fn encoded() -> (PoseidonConstants<Fr, U2>, Vec<u8>) {
+        let constants = PoseidonConstants::<Fr, U2>::new();
+        let mut bytes = Vec::new();
+        write_constants(&constants, &mut bytes).unwrap();
+        (constants, bytes)
+    }
+
+    #[test]
+    fn roundtrip() {
+        let (constants, bytes) = encoded();
+        let header = Header::from_bytes(bytes[..HEADER_LEN].try_into().unwrap()).unwrap();
+        assert_eq!(header.version, FORMAT_VERSION);
+        assert_eq!(header.field_id, field_id::<Fr>());
+        assert_eq!(header.body_len as usize, bytes.len() - HEADER_LEN);
+        assert_eq!(encoded_len(&constants), bytes.len());
+
+        let decoded: PoseidonConstants<Fr, U2> = read_constants(&bytes[..]).unwrap();
+
+        let preimage = [Fr::ONE, Fr::ONE];
+        assert_eq!(
+            Poseidon::new_with_preimage(&preimage, &constants).hash(),
+            Poseidon::new_with_preimage(&preimage, &decoded).hash()
+        );
+    }
+
+    #[test]
+    fn custom_domain_tag() {
+        let mut constants = PoseidonConstants::<Fr, U2>::new_with_domain_tag(Fr::from(1234));
+        constants.round_constants = None;
+
+        let mut bytes = Vec::new();
+        write_constants(&constants, &mut bytes).unwrap();
+        assert_eq!(encoded_len(&constants), bytes.len());
+        let decoded: PoseidonConstants<Fr, U2> = read_constants(&bytes[..]).unwrap();
+        assert_eq!(constants, decoded);
+        assert_eq!(Fr::from(1234), decoded.domain_tag());
+
+        // Plain bincode keeps the released layout, which has no room for the tag.
+        let plain: PoseidonConstants<Fr, U2> =
+            bincode::deserialize(&bincode::serialize(&constants).unwrap()).unwrap();
+        assert_eq!(
+            PoseidonConstants::<Fr, U2>::new().domain_tag(),
+            plain.domain_tag()
+        );
+    }
+
+    #[test]
+    fn reads_version_2() {
+        // Version 2 bodies are the constants alone.
+        let mut constants = PoseidonConstants::<Fr, U2>::new();
+        constants.round_constants = None;
+        let body = bincode::serialize(&constants).unwrap();
+        let header = Header {
+            version: 2,
+            field_id: field_id::<Fr>(),
+            body_len: body.len() as u64,
+        };
+        let bytes = [&header.to_bytes()[..], &body].concat();
+
+        assert_eq!(constants, read_constants::<Fr, U2, _>(&bytes[..]).unwrap());
+    }
+
+    #[test]
+    fn format_errors() {
+        let (_, bytes) = encoded();
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] ^= 1;
+        assert!(matches!(
+            read_constants::<Fr, U2, _>(&bad_magic[..]),
+            Err(Error::Format(_))
+        ));
+
+        let mut old_version = bytes.clone();
+        LittleEndian::write_u16(&mut old_version[4..6], 1);
+        assert!(matches!(
+            read_constants::<Fr, U2, _>(&old_version[..]),
+            Err(Error::Format(_))
+        ));
+
+        assert!(matches!(
+            read_constants::<Fr, U2, _>(&bytes[..HEADER_LEN - 1]),
+            Err(Error::Format(_))
+        ));
+        assert!(matches!(
+            read_constants::<Fr, U2, _>(&bytes[..bytes.len() - 1]),
+            Err(Error::Format(_))
+        ));
+    }
+
+    #[test]
+    fn newer_version() {
+        let (_, mut bytes) = encoded();
+        assert_eq!(FORMAT_VERSION, peek_version(&bytes).unwrap());
+
+        LittleEndian::write_u16(&mut bytes[4..6], FORMAT_VERSION + 1);
+        assert_eq!(FORMAT_VERSION + 1, peek_version(&bytes[..6]).unwrap());
+        assert!(matches!(
+            read_constants::<Fr, U2, _>(&bytes[..]),
+            Err(Error::UnsupportedVersion { found, max_supported })
+                if found == FORMAT_VERSION + 1 && max_supported == FORMAT_VERSION
+        ));
+
+        assert!(matches!(peek_version(&bytes[..5]), Err(Error::Format(_))));
+        bytes[0] ^= 1;
+        assert!(matches!(peek_version(&bytes), Err(Error::Format(_))));
+    }
+
+    #[test]
+    fn field_mismatch() {
+        let (_, bytes) = encoded();
+        assert_ne!(field_id::<Fr>(), field_id::<Fp>());
+        assert!(matches!(
+            read_constants::<Fp, U2, _>(&bytes[..]),
+            Err(Error::FieldMismatch)
+        ));
+    }
+
+    #[cfg(feature = "tokio")]
+    mod async_tests {
+        use super::*;
+        use std::pin::Pin;
+        use std::task::{Context, Poll};
+        use tokio::io::{AsyncRead, ReadBuf};
+
+        /// Serves its data at most `chunk` bytes at a time, then fails if `fail` is set.
+        struct ChunkedReader {
+            data: Vec<u8>,
+            pos: usize,
+            chunk: usize,
+            fail: bool,
+        }
+
+        impl AsyncRead for ChunkedReader {
+            fn poll_read(
+                mut self: Pin<&mut Self>,
+                _cx: &mut Context<'_>,
+                buf: &mut ReadBuf<'_>,
+            ) -> Poll<io::Result<()>> {
+                if self.pos == self.data.len() && self.fail {
+                    return Poll::Ready(Err(io::Error::new(
+                        io::ErrorKind::ConnectionReset,
+                        "connection reset",
+                    )));
+                }
+                let start = self.pos;
+                let end = (start + self.chunk)
+                    .min(self.data.len())
+                    .min(start + buf.remaining());
+                buf.put_slice(&self.data[start..end]);
+                self.pos = end;
+                Poll::Ready(Ok(()))
+            }
+        }
+
+        #[tokio::test]
+        async fn load_in_small_chunks() {
+            let (constants, bytes) = encoded();
+            for chunk in [1, 3, 7, 64] {
+                let reader = ChunkedReader {
+                    data: bytes.clone(),
+                    pos: 0,
+                    chunk,
+                    fail: false,
+                };
+                let loaded: PoseidonConstants<Fr, U2> = load_constants_async(reader).await.unwrap();
+                assert_eq!(
+                    constants.compressed_round_constants,
+                    loaded.compressed_round_constants
+                );
+                assert_eq!(constants.mds_matrices, loaded.mds_matrices);
+            }
+        }
+
+        #[tokio::test]
+        async fn io_and_format_errors_are_distinct() {
+            let (_, bytes) = encoded();
+
+            let failing = ChunkedReader {
+                data: bytes[..20].to_vec(),
+                pos: 0,
+                chunk: 5,
+                fail: true,
+            };
+            assert!(matches!(
+                load_constants_async::<Fr, U2, _>(failing).await,
+                Err(Error::Io(_))
+            ));
+
+            let truncated = ChunkedReader {
+                data: bytes[..20].to_vec(),
+                pos: 0,
+                chunk: 5,
+                fail: false,
+            };
+            assert!(matches!(
+                load_constants_async::<Fr, U2, _>(truncated).await,
+                Err(Error::Format(_))
+            ));
+        }
+    }
+}